@@ -0,0 +1,235 @@
+use core::time::Duration;
+use futures::{
+    future::{self, BoxFuture, FutureExt},
+    stream::{self, StreamExt},
+};
+use serde::de::DeserializeOwned;
+use crate::client::{AuthenticatedClient, ORD, QTY};
+use crate::error::{Error, Kind};
+
+/// Price and quantity of an [OrderRequest](struct.OrderRequest.html)
+#[derive(Debug, Clone, Copy)]
+pub enum OrderKind {
+    Limit { price: f64, size: f64 },
+    Market(QTY),
+}
+
+/// Owned order parameters for [place_orders](../client/struct.AuthenticatedClient.html#method.place_orders)
+#[derive(Debug, Clone)]
+pub struct OrderRequest {
+    pub product_id: String,
+    pub side: String,
+    pub kind: OrderKind,
+    pub client_oid: Option<String>,
+    pub stp: Option<String>,
+    pub time_in_force: Option<String>,
+    pub cancel_after: Option<String>,
+    pub post_only: Option<bool>,
+    pub stop_price: Option<f64>,
+}
+
+impl OrderRequest {
+    /// Creates a limit order.
+    pub fn limit<P: Into<String>, S: Into<String>>(product_id: P, side: S, price: f64, size: f64) -> Self {
+        Self::new(product_id.into(), side.into(), OrderKind::Limit { price, size })
+    }
+
+    /// Creates a market order, sending it fails when options other than `client_oid` are set.
+    pub fn market<P: Into<String>, S: Into<String>>(product_id: P, side: S, qty: QTY) -> Self {
+        Self::new(product_id.into(), side.into(), OrderKind::Market(qty))
+    }
+
+    fn new(product_id: String, side: String, kind: OrderKind) -> Self {
+        Self {
+            product_id,
+            side,
+            kind,
+            client_oid: None,
+            stp: None,
+            time_in_force: None,
+            cancel_after: None,
+            post_only: None,
+            stop_price: None,
+        }
+    }
+
+    /// Sets the client oid.
+    pub fn client_oid<C: Into<String>>(mut self, value: C) -> Self {
+        self.client_oid = Some(value.into());
+        self
+    }
+
+    /// Sets the self-trade prevention flag of a limit order.
+    pub fn stp<V: Into<String>>(mut self, value: V) -> Self {
+        self.stp = Some(value.into());
+        self
+    }
+
+    /// Sets the time in force of a limit order.
+    pub fn time_in_force<V: Into<String>>(mut self, value: V) -> Self {
+        self.time_in_force = Some(value.into());
+        self
+    }
+
+    /// Sets cancel after of a GTT limit order.
+    pub fn cancel_after<V: Into<String>>(mut self, value: V) -> Self {
+        self.cancel_after = Some(value.into());
+        self
+    }
+
+    /// Sets the post only flag of a limit order.
+    pub fn post_only(mut self, value: bool) -> Self {
+        self.post_only = Some(value);
+        self
+    }
+
+    /// Turns a limit order into a stop loss, or a stop entry for a buy.
+    pub fn stop_price(mut self, value: f64) -> Self {
+        self.stop_price = Some(value);
+        self
+    }
+
+    fn text<'b>(&'b self, client: &'b AuthenticatedClient) -> BoxFuture<'b, crate::error::Result<String>> {
+        match self.kind {
+            OrderKind::Limit { price, size } => {
+                let mut builder = client.place_limit_order(&self.product_id, &self.side, price, size);
+                if let Some(ref value) = self.client_oid {
+                    builder = builder.client_oid(value);
+                }
+                if let Some(ref value) = self.stp {
+                    builder = builder.stp(value);
+                }
+                if let Some(ref value) = self.time_in_force {
+                    builder = builder.time_in_force(value);
+                }
+                if let Some(ref value) = self.cancel_after {
+                    builder = builder.cancel_after(value);
+                }
+                if let Some(value) = self.post_only {
+                    builder = builder.post_only(value);
+                }
+                if let Some(value) = self.stop_price {
+                    builder = builder.stop_price(value);
+                }
+                builder.text().boxed()
+            }
+            OrderKind::Market(qty) => {
+                let options = [
+                    ("stp", self.stp.is_some()),
+                    ("time_in_force", self.time_in_force.is_some()),
+                    ("cancel_after", self.cancel_after.is_some()),
+                    ("post_only", self.post_only.is_some()),
+                    ("stop_price", self.stop_price.is_some()),
+                ];
+                let unsupported: Vec<&str> = options.iter().filter(|(_, set)| *set).map(|(name, _)| *name).collect();
+                if !unsupported.is_empty() {
+                    let message = format!("market orders do not take {}", unsupported.join(", "));
+                    return future::ready(Err(Error::new(Kind::Order, Some(message)))).boxed();
+                }
+                let mut builder = client.place_market_order(&self.product_id, &self.side, qty);
+                if let Some(ref value) = self.client_oid {
+                    builder = builder.client_oid(value);
+                }
+                builder.text().boxed()
+            }
+        }
+    }
+}
+
+/// Single request of a [Batch](struct.Batch.html)
+pub trait BatchItem: Sync {
+    #[doc(hidden)]
+    fn send<'b>(&'b self, client: &'b AuthenticatedClient) -> BoxFuture<'b, crate::error::Result<String>>;
+}
+
+impl BatchItem for OrderRequest {
+    fn send<'b>(&'b self, client: &'b AuthenticatedClient) -> BoxFuture<'b, crate::error::Result<String>> {
+        self.text(client)
+    }
+}
+
+impl<'a> BatchItem for ORD<'a> {
+    fn send<'b>(&'b self, client: &'b AuthenticatedClient) -> BoxFuture<'b, crate::error::Result<String>> {
+        client.cancel_order(*self).text().boxed()
+    }
+}
+
+/// Requests sent with bounded concurrency and spacing, returned by
+/// [place_orders](../client/struct.AuthenticatedClient.html#method.place_orders) and
+/// [cancel_orders](../client/struct.AuthenticatedClient.html#method.cancel_orders).
+///
+/// Defaults to 5 requests in flight and 5 requests per second, the rate limit of private endpoints.
+/// Requests answered with status 429 are retried.
+pub struct Batch<I> {
+    client: AuthenticatedClient,
+    inputs: Vec<I>,
+    concurrency: usize,
+    rate_limit: f64,
+    retries: u32,
+}
+
+impl<I: BatchItem> Batch<I> {
+    pub(super) fn new(client: AuthenticatedClient, inputs: Vec<I>) -> Self {
+        Self {
+            client,
+            inputs,
+            concurrency: 5,
+            rate_limit: 5.0,
+            retries: 3,
+        }
+    }
+
+    /// Sets the number of requests in flight.
+    pub fn concurrency(mut self, value: usize) -> Self {
+        self.concurrency = value.max(1);
+        self
+    }
+
+    /// Sets the number of requests started per second.
+    pub fn rate_limit(mut self, per_second: f64) -> Self {
+        self.rate_limit = per_second;
+        self
+    }
+
+    /// Sets how often a request answered with status 429 is retried, defaults to 3.
+    pub fn retries(mut self, value: u32) -> Self {
+        self.retries = value;
+        self
+    }
+
+    /// Sends all requests and returns each input paired with its result, in input order.
+    /// Failed requests do not abort the batch.
+    pub async fn json<J: DeserializeOwned>(self) -> Vec<(I, crate::error::Result<J>)> {
+        let Batch { client, inputs, concurrency, rate_limit, retries } = self;
+        let interval = if rate_limit > 0.0 {
+            Duration::from_secs_f64(1.0 / rate_limit)
+        } else {
+            Duration::from_secs(0)
+        };
+        let start = tokio::time::Instant::now();
+        let client = &client;
+
+        let mut results: Vec<(usize, crate::error::Result<J>)> = stream::iter(inputs.iter().enumerate())
+            .map(|(index, input)| async move {
+                tokio::time::delay_until(start + interval * index as u32).await;
+                let mut attempt = 0;
+                let result = loop {
+                    match input.send(client).await {
+                        Err(ref error) if error.status() == Some(429) && attempt < retries => {
+                            attempt += 1;
+                            tokio::time::delay_for(interval.max(Duration::from_secs(1))).await;
+                        }
+                        result => break result,
+                    }
+                };
+                let result = result.and_then(|text| Ok(serde_json::from_str(&text)?));
+                (index, result)
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+        results.sort_by_key(|(index, _)| *index);
+        inputs.into_iter().zip(results.into_iter().map(|(_, result)| result)).collect()
+    }
+}
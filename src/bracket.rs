@@ -0,0 +1,713 @@
+use core::time::Duration;
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::batch::OrderRequest;
+use crate::client::{AuthenticatedClient, ORD};
+use crate::models::{FeedMessage, Order};
+use crate::replace::ReplaceOutcome;
+use crate::websocket::{Channels, WebSocketFeed};
+
+// sizes below are float residue of partial fills
+const EPSILON: f64 = 1e-9;
+
+/// State of a [Bracket](struct.Bracket.html)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BracketState {
+    /// The position is not fully exited yet and at least one leg is working
+    Active,
+    /// The position was exited, mostly by the take profit
+    TakeProfit,
+    /// The position was exited, mostly by the stop loss
+    StopLoss,
+    /// Both legs were canceled before the position was fully exited, partial fills are kept on the legs
+    Canceled,
+}
+
+/// Order of a [Bracket](struct.Bracket.html)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Leg {
+    /// Set before the order is placed so it can be found after a restart
+    pub client_oid: String,
+    pub order_id: Option<String>,
+    pub price: f64,
+    /// Set on the stop loss
+    pub stop_price: Option<f64>,
+    /// Filled by all orders of the leg
+    pub filled_size: f64,
+    pub done: bool,
+    /// Size of the current order once the leg was resized to the unfilled rest of the position
+    #[serde(default)]
+    pub order_size: Option<f64>,
+    /// Filled by orders of the leg replaced while resizing
+    #[serde(default)]
+    pub resized_filled: f64,
+}
+
+impl Leg {
+    fn new(price: f64, stop_price: Option<f64>) -> Self {
+        Self {
+            client_oid: Uuid::new_v4().to_string(),
+            order_id: None,
+            price,
+            stop_price,
+            filled_size: 0.0,
+            done: false,
+            order_size: None,
+            resized_filled: 0.0,
+        }
+    }
+
+    /// Unfilled size of the current order.
+    fn open_size(&self, bracket_size: f64) -> f64 {
+        self.order_size.unwrap_or(bracket_size) - (self.filled_size - self.resized_filled)
+    }
+
+    /// Records the filled size of the current order.
+    fn order_filled(&mut self, filled_size: f64) {
+        self.filled_size = self.filled_size.max(self.resized_filled + filled_size);
+    }
+
+    fn ord(&self) -> ORD<'_> {
+        match self.order_id {
+            Some(ref id) => ORD::OrderID(id),
+            None => ORD::ClientOID(&self.client_oid),
+        }
+    }
+}
+
+/// Take profit and stop loss exiting a position, persisted by the [BracketManager](struct.BracketManager.html)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bracket {
+    pub id: String,
+    pub product_id: String,
+    /// Side of both legs, `sell` exits a long position
+    pub side: String,
+    pub size: f64,
+    pub take_profit: Leg,
+    pub stop_loss: Leg,
+    pub state: BracketState,
+}
+
+impl Bracket {
+    /// Returns whether the bracket is still supervised.
+    pub fn is_active(&self) -> bool {
+        self.state == BracketState::Active
+    }
+
+    /// Returns the size of the position not exited yet.
+    pub fn remaining(&self) -> f64 {
+        self.size - self.take_profit.filled_size - self.stop_loss.filled_size
+    }
+
+    fn finished_state(&self) -> BracketState {
+        if self.remaining() > EPSILON {
+            BracketState::Canceled
+        } else if self.take_profit.filled_size >= self.stop_loss.filled_size {
+            BracketState::TakeProfit
+        } else {
+            BracketState::StopLoss
+        }
+    }
+
+    /// Decides how the working legs follow the fills.
+    fn plan(&self) -> Plan {
+        let legs = [(Side::TakeProfit, &self.take_profit), (Side::StopLoss, &self.stop_loss)];
+        let working: Vec<Side> = legs.iter().filter(|(_, leg)| !leg.done).map(|(side, _)| *side).collect();
+        let remaining = self.remaining();
+        let filled = self.take_profit.filled_size > EPSILON || self.stop_loss.filled_size > EPSILON;
+
+        if remaining <= EPSILON || working.is_empty() || (!filled && working.len() == 1) {
+            // exited, or a leg was canceled outside of the manager before any fill and the other has no partner
+            return Plan { actions: working.into_iter().map(Action::Cancel).collect(), finish: true };
+        }
+        // partially exited, the working legs are cut to the rest of the position
+        let actions = legs
+            .iter()
+            .filter(|(_, leg)| !leg.done && leg.open_size(self.size) > remaining + EPSILON)
+            .map(|(side, _)| Action::Resize(*side, remaining))
+            .collect();
+        Plan { actions, finish: false }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    TakeProfit,
+    StopLoss,
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    Cancel(Side),
+    /// Replaces the order of a leg with one of the given size
+    Resize(Side, f64),
+}
+
+struct Plan {
+    actions: Vec<Action>,
+    /// Whether the bracket is done once the actions ran
+    finish: bool,
+}
+
+/// Client side one-cancels-other of a take profit limit order and a stop loss order.
+///
+/// Fills of either leg shrink the other leg to the unfilled rest of the position with a cancel/replace,
+/// once the position is fully exited the other leg is canceled.
+/// Fills are watched on the `user` channel, while the socket is down and after every (re)connect the legs are
+/// reconciled over REST. All brackets are written to a json file on every change, [load](struct.BracketManager.html#method.load)
+/// resumes supervision after a restart and places legs that were not placed yet.
+///
+/// Coinbase holds funds for both legs, the account needs twice the size available.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::bracket::BracketManager;
+/// use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+/// use cbpro::websocket::SANDBOX_FEED_URL;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+/// let mut manager = BracketManager::load(client, SANDBOX_FEED_URL, "brackets.json").await?;
+///
+/// manager.open("BTC-USD", "sell", 0.1, 7500.00, 6500.00, 6450.00).await?;
+///
+/// loop {
+///     let bracket = manager.next().await?;
+///     println!("{} {:?}", bracket.id, bracket.state);
+/// }
+/// # }
+/// ```
+pub struct BracketManager {
+    client: AuthenticatedClient,
+    url: String,
+    path: PathBuf,
+    brackets: Vec<Bracket>,
+    feed: Option<WebSocketFeed>,
+    subscribed: HashSet<String>,
+    updates: VecDeque<Bracket>,
+    poll_interval: Duration,
+    reconnect_interval: Duration,
+    stale_after: Duration,
+    connected_at: Option<Instant>,
+}
+
+impl BracketManager {
+    /// Loads the brackets persisted at `path`, a missing file starts empty.
+    /// Active brackets are reconciled on the first call to `next`.
+    pub async fn load<U, P>(client: AuthenticatedClient, feed_url: U, path: P) -> crate::error::Result<Self>
+    where
+        U: Into<String>,
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let brackets = match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Self {
+            client,
+            url: feed_url.into(),
+            path,
+            brackets,
+            feed: None,
+            subscribed: HashSet::new(),
+            updates: VecDeque::new(),
+            poll_interval: Duration::from_secs(5),
+            reconnect_interval: Duration::from_secs(10),
+            stale_after: Duration::from_secs(30),
+            connected_at: None,
+        })
+    }
+
+    /// Sets the delay between REST polls while the socket is down, defaults to 5 seconds.
+    pub fn poll_interval(mut self, value: Duration) -> Self {
+        self.poll_interval = value;
+        self
+    }
+
+    /// Sets the delay between connection attempts, defaults to 10 seconds.
+    pub fn reconnect_interval(mut self, value: Duration) -> Self {
+        self.reconnect_interval = value;
+        self
+    }
+
+    /// Sets the time without any message after which the socket is considered down, defaults to 30 seconds.
+    pub fn stale_after(mut self, value: Duration) -> Self {
+        self.stale_after = value;
+        self
+    }
+
+    /// Returns all brackets, including finished ones.
+    pub fn brackets(&self) -> &[Bracket] {
+        &self.brackets
+    }
+
+    /// Returns a bracket by id.
+    pub fn bracket(&self, id: &str) -> Option<&Bracket> {
+        self.brackets.iter().find(|bracket| bracket.id == id)
+    }
+
+    /// Places a take profit limit order at `take_profit` and a stop loss limit order at `stop_limit`
+    /// triggered at `stop_price`, both of `size`. Both legs are held by Coinbase, `size` must be available twice.
+    pub async fn open(
+        &mut self,
+        product_id: &str,
+        side: &str,
+        size: f64,
+        take_profit: f64,
+        stop_price: f64,
+        stop_limit: f64,
+    ) -> crate::error::Result<Bracket> {
+        self.brackets.push(Bracket {
+            id: Uuid::new_v4().to_string(),
+            product_id: product_id.to_string(),
+            side: side.to_string(),
+            size,
+            take_profit: Leg::new(take_profit, None),
+            stop_loss: Leg::new(stop_limit, Some(stop_price)),
+            state: BracketState::Active,
+        });
+        self.save().await?;
+
+        let index = self.brackets.len() - 1;
+        let placed = match self.place(index, Side::TakeProfit).await {
+            Ok(()) => self.place(index, Side::StopLoss).await,
+            Err(error) => Err(error),
+        };
+        // a rejected leg is not retried, the bracket is abandoned
+        if let Err(error) = placed {
+            self.cancel_leg(index, Side::TakeProfit).await.ok();
+            self.brackets[index].stop_loss.done = true;
+            self.brackets[index].state = BracketState::Canceled;
+            self.save().await?;
+            return Err(error);
+        }
+        self.save().await?;
+
+        if let Some(ref mut feed) = self.feed {
+            if self.subscribed.insert(product_id.to_string()) {
+                feed.subscribe(&[product_id], &[Channels::USER, Channels::HEARTBEAT]).await?;
+            }
+        }
+        Ok(self.brackets[index].clone())
+    }
+
+    /// Cancels both legs of an active bracket and stops supervising it.
+    pub async fn cancel(&mut self, id: &str) -> crate::error::Result<Option<Bracket>> {
+        let index = match self.brackets.iter().position(|bracket| bracket.id == id && bracket.is_active()) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        self.cancel_leg(index, Side::TakeProfit).await?;
+        self.cancel_leg(index, Side::StopLoss).await?;
+
+        let bracket = &mut self.brackets[index];
+        bracket.state = bracket.finished_state();
+        self.save().await?;
+        Ok(Some(self.brackets[index].clone()))
+    }
+
+    /// Waits for the next change of a bracket and returns its new state.
+    pub async fn next(&mut self) -> crate::error::Result<Bracket> {
+        loop {
+            if let Some(bracket) = self.updates.pop_front() {
+                return Ok(bracket);
+            }
+
+            if let Some(mut feed) = self.feed.take() {
+                if let Ok(Ok(Some(value))) = tokio::time::timeout(self.stale_after, feed.json::<serde_json::Value>()).await {
+                    self.feed = Some(feed);
+                    self.apply(&value).await?;
+                }
+                continue;
+            }
+
+            let due = match self.connected_at {
+                Some(at) => at.elapsed() >= self.reconnect_interval,
+                None => true,
+            };
+            if due && self.brackets.iter().any(Bracket::is_active) {
+                self.connected_at = Some(Instant::now());
+                self.feed = self.connect().await.ok();
+            }
+
+            self.reconcile().await?;
+            if self.feed.is_none() && self.updates.is_empty() {
+                tokio::time::delay_for(self.poll_interval).await;
+            }
+        }
+    }
+
+    async fn connect(&mut self) -> crate::error::Result<WebSocketFeed> {
+        let product_ids: HashSet<String> = self
+            .brackets
+            .iter()
+            .filter(|bracket| bracket.is_active())
+            .map(|bracket| bracket.product_id.clone())
+            .collect();
+        let auth = self.client.auth();
+        let mut feed = WebSocketFeed::connect_auth(auth.key.clone(), auth.pass.clone(), auth.secret.clone(), self.url.clone()).await?;
+        let subscribed: Vec<&str> = product_ids.iter().map(String::as_str).collect();
+        // heartbeats keep the feed busy so a silent socket is detected as stale
+        feed.subscribe(&subscribed, &[Channels::USER, Channels::HEARTBEAT]).await?;
+        self.subscribed = product_ids;
+        Ok(feed)
+    }
+
+    fn leg(&mut self, index: usize, side: Side) -> &mut Leg {
+        let bracket = &mut self.brackets[index];
+        match side {
+            Side::TakeProfit => &mut bracket.take_profit,
+            Side::StopLoss => &mut bracket.stop_loss,
+        }
+    }
+
+    async fn place(&mut self, index: usize, side: Side) -> crate::error::Result<()> {
+        let bracket = &self.brackets[index];
+        let leg = match side {
+            Side::TakeProfit => &bracket.take_profit,
+            Side::StopLoss => &bracket.stop_loss,
+        };
+        let size = leg.order_size.unwrap_or(bracket.size);
+        let mut builder = self
+            .client
+            .place_limit_order(&bracket.product_id, &bracket.side, leg.price, size)
+            .client_oid(&leg.client_oid);
+        if let Some(stop_price) = leg.stop_price {
+            builder = builder.stop_price(stop_price);
+        }
+        let order: Order = builder.json().await?;
+        self.leg(index, side).order_id = Some(order.id);
+        Ok(())
+    }
+
+    async fn cancel_leg(&mut self, index: usize, side: Side) -> crate::error::Result<()> {
+        let leg = self.leg(index, side).clone();
+        if leg.done {
+            return Ok(());
+        }
+        match self.client.cancel_order(leg.ord()).text().await {
+            Ok(_) => (),
+            Err(ref error) if matches!(error.status(), Some(400) | Some(404)) => (),
+            Err(error) => return Err(error),
+        }
+        // the leg may have filled before the cancel arrived
+        let filled_size = match self.client.get_order(leg.ord()).json::<Order>().await {
+            Ok(order) => order.filled_size,
+            Err(ref error) if error.status() == Some(404) => 0.0,
+            Err(error) => return Err(error),
+        };
+        let leg = self.leg(index, side);
+        leg.order_filled(filled_size);
+        leg.done = true;
+        Ok(())
+    }
+
+    /// Replaces the order of a leg with one of `size`.
+    async fn resize_leg(&mut self, index: usize, side: Side, size: f64) -> crate::error::Result<()> {
+        let bracket = &self.brackets[index];
+        let leg = match side {
+            Side::TakeProfit => &bracket.take_profit,
+            Side::StopLoss => &bracket.stop_loss,
+        };
+        let order_id = match leg.order_id {
+            Some(ref order_id) => order_id.clone(),
+            // not placed yet, placed with the new size
+            None => {
+                self.leg(index, side).order_size = Some(size);
+                return Ok(());
+            }
+        };
+        let client_oid = Uuid::new_v4().to_string();
+        let mut request = OrderRequest::limit(bracket.product_id.clone(), bracket.side.clone(), leg.price, size).client_oid(client_oid.clone());
+        if let Some(stop_price) = leg.stop_price {
+            request = request.stop_price(stop_price);
+        }
+        let outcome = self.client.replace_order(ORD::OrderID(&order_id), request).send().await?;
+
+        let leg = self.leg(index, side);
+        match outcome {
+            ReplaceOutcome::Replaced { canceled, placed } => {
+                if let Some(canceled) = canceled {
+                    leg.order_filled(canceled.filled_size);
+                }
+                leg.resized_filled = leg.filled_size;
+                leg.client_oid = client_oid;
+                leg.order_id = Some(placed.id);
+                leg.order_size = Some(size);
+            }
+            // filled while canceled, the next settle cuts the new order again
+            ReplaceOutcome::PartiallyFilled { canceled, placed } => {
+                leg.order_filled(canceled.filled_size);
+                leg.resized_filled = leg.filled_size;
+                leg.client_oid = client_oid;
+                leg.order_id = Some(placed.id);
+                leg.order_size = Some(size);
+            }
+            ReplaceOutcome::Filled { order } => {
+                leg.order_filled(order.filled_size);
+                leg.done = true;
+            }
+            ReplaceOutcome::NotFound => leg.done = true,
+            // the old order may still rest, the next settle tries again
+            ReplaceOutcome::Unconfirmed { .. } => (),
+            ReplaceOutcome::PlaceFailed { canceled, error } => {
+                if let Some(canceled) = canceled {
+                    leg.order_filled(canceled.filled_size);
+                }
+                leg.done = true;
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Polls the legs of all active brackets over REST, places legs that were never placed.
+    async fn reconcile(&mut self) -> crate::error::Result<()> {
+        for index in 0..self.brackets.len() {
+            if !self.brackets[index].is_active() {
+                continue;
+            }
+            let before = self.brackets[index].clone();
+            for &side in &[Side::TakeProfit, Side::StopLoss] {
+                let leg = self.leg(index, side).clone();
+                if leg.done {
+                    continue;
+                }
+                match self.client.get_order(leg.ord()).json::<Order>().await {
+                    Ok(order) => {
+                        let leg = self.leg(index, side);
+                        leg.order_id = Some(order.id);
+                        leg.order_filled(order.filled_size);
+                        leg.done = order.status == "done" || order.status == "rejected";
+                    }
+                    // never placed, the process stopped between saving and placing
+                    Err(ref error) if error.status() == Some(404) && leg.order_id.is_none() => self.place(index, side).await?,
+                    // canceled without fills outside of the manager
+                    Err(ref error) if error.status() == Some(404) => self.leg(index, side).done = true,
+                    Err(error) => return Err(error),
+                }
+            }
+            self.settle(index).await?;
+            if self.brackets[index] != before {
+                self.save().await?;
+                self.updates.push_back(self.brackets[index].clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Cuts or cancels the working legs after fills, finishes the bracket once the position is exited or both legs are done.
+    async fn settle(&mut self, index: usize) -> crate::error::Result<()> {
+        if !self.brackets[index].is_active() {
+            return Ok(());
+        }
+        let plan = self.brackets[index].plan();
+        for action in plan.actions {
+            match action {
+                Action::Cancel(side) => self.cancel_leg(index, side).await?,
+                Action::Resize(side, size) => self.resize_leg(index, side, size).await?,
+            }
+        }
+        if plan.finish {
+            let bracket = &mut self.brackets[index];
+            bracket.state = bracket.finished_state();
+        }
+        Ok(())
+    }
+
+    async fn apply(&mut self, value: &serde_json::Value) -> crate::error::Result<()> {
+        let message: FeedMessage = serde_json::from_value(value.clone())?;
+        let ids = [&message.order_id, &message.maker_order_id, &message.taker_order_id];
+        let found = self.brackets.iter().enumerate().filter(|(_, bracket)| bracket.is_active()).find_map(|(index, bracket)| {
+            if bracket.take_profit.order_id.is_some() && ids.contains(&&bracket.take_profit.order_id) {
+                Some((index, Side::TakeProfit))
+            } else if bracket.stop_loss.order_id.is_some() && ids.contains(&&bracket.stop_loss.order_id) {
+                Some((index, Side::StopLoss))
+            } else {
+                None
+            }
+        });
+        let (index, side) = match found {
+            Some(found) => found,
+            None => return Ok(()),
+        };
+
+        let before = self.brackets[index].clone();
+        let leg = self.leg(index, side);
+        match message.type_.as_str() {
+            "match" => leg.filled_size += message.size.unwrap_or(0.0),
+            "done" => leg.done = true,
+            _ => (),
+        }
+        self.settle(index).await?;
+
+        if self.brackets[index] != before {
+            self.save().await?;
+            self.updates.push_back(self.brackets[index].clone());
+        }
+        Ok(())
+    }
+
+    /// Writes all brackets to a temporary file and moves it over the previous state.
+    async fn save(&self) -> crate::error::Result<()> {
+        let bytes = serde_json::to_vec_pretty(&self.brackets)?;
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        tokio::fs::write(&temporary, bytes).await?;
+        tokio::fs::rename(&temporary, &self.path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SANDBOX_URL;
+    use crate::websocket::SANDBOX_FEED_URL;
+
+    fn bracket(take_profit_filled: f64, stop_loss_filled: f64) -> Bracket {
+        let mut take_profit = Leg::new(110.0, None);
+        take_profit.order_id = Some("take-profit".to_string());
+        take_profit.filled_size = take_profit_filled;
+        let mut stop_loss = Leg::new(89.0, Some(90.0));
+        stop_loss.order_id = Some("stop-loss".to_string());
+        stop_loss.filled_size = stop_loss_filled;
+        Bracket {
+            id: "bracket".to_string(),
+            product_id: "BTC-USD".to_string(),
+            side: "sell".to_string(),
+            size: 1.0,
+            take_profit,
+            stop_loss,
+            state: BracketState::Active,
+        }
+    }
+
+    #[test]
+    fn no_fills_keep_both_legs() {
+        let plan = bracket(0.0, 0.0).plan();
+        assert!(plan.actions.is_empty());
+        assert!(!plan.finish);
+    }
+
+    #[test]
+    fn partial_fill_cuts_the_sibling_to_the_rest() {
+        let plan = bracket(0.4, 0.0).plan();
+        assert_eq!(plan.actions, vec![Action::Resize(Side::StopLoss, 0.6)]);
+        assert!(!plan.finish);
+    }
+
+    #[test]
+    fn resized_sibling_is_left_alone() {
+        let mut bracket = bracket(0.4, 0.0);
+        bracket.stop_loss.order_size = Some(0.6);
+        assert!(bracket.plan().actions.is_empty());
+
+        // the resized leg fills partially as well, the take profit follows
+        bracket.stop_loss.filled_size = 0.1;
+        let plan = bracket.plan();
+        assert_eq!(plan.actions, vec![Action::Resize(Side::TakeProfit, 0.5)]);
+    }
+
+    #[test]
+    fn full_fill_cancels_the_sibling() {
+        let mut bracket = bracket(0.7, 0.0);
+        bracket.take_profit.filled_size += 0.3;
+        let plan = bracket.plan();
+        assert_eq!(plan.actions, vec![Action::Cancel(Side::TakeProfit), Action::Cancel(Side::StopLoss)]);
+        assert!(plan.finish);
+
+        bracket.take_profit.done = true;
+        assert_eq!(bracket.plan().actions, vec![Action::Cancel(Side::StopLoss)]);
+        assert_eq!(bracket.finished_state(), BracketState::TakeProfit);
+    }
+
+    #[test]
+    fn leg_canceled_outside_without_fills_cancels_the_other() {
+        let mut bracket = bracket(0.0, 0.0);
+        bracket.stop_loss.done = true;
+        let plan = bracket.plan();
+        assert_eq!(plan.actions, vec![Action::Cancel(Side::TakeProfit)]);
+        assert!(plan.finish);
+        assert_eq!(bracket.finished_state(), BracketState::Canceled);
+    }
+
+    #[test]
+    fn partially_exited_bracket_keeps_the_working_leg() {
+        let mut bracket = bracket(0.0, 0.5);
+        bracket.stop_loss.done = true;
+        bracket.take_profit.order_size = Some(0.5);
+        let plan = bracket.plan();
+        assert!(plan.actions.is_empty());
+        assert!(!plan.finish);
+
+        bracket.take_profit.done = true;
+        assert!(bracket.plan().finish);
+        assert_eq!(bracket.finished_state(), BracketState::Canceled);
+    }
+
+    #[test]
+    fn float_residue_counts_as_exited() {
+        let bracket = bracket(0.1 + 0.2, 0.7);
+        assert!(bracket.plan().finish);
+        assert_eq!(bracket.finished_state(), BracketState::StopLoss);
+    }
+
+    #[test]
+    fn order_fills_add_to_fills_of_replaced_orders() {
+        let mut leg = Leg::new(110.0, None);
+        leg.order_filled(0.4);
+        leg.resized_filled = leg.filled_size;
+        leg.order_size = Some(0.6);
+        assert!((leg.open_size(1.0) - 0.6).abs() < EPSILON);
+
+        leg.order_filled(0.1);
+        assert!((leg.filled_size - 0.5).abs() < EPSILON);
+        assert!((leg.open_size(1.0) - 0.5).abs() < EPSILON);
+    }
+
+    fn client() -> AuthenticatedClient {
+        AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL)
+    }
+
+    #[tokio::test]
+    async fn resumes_from_persisted_state() {
+        let path = std::env::temp_dir().join(format!("cbpro-brackets-{}.json", Uuid::new_v4()));
+        let mut manager = BracketManager::load(client(), SANDBOX_FEED_URL, &path).await.unwrap();
+        assert!(manager.brackets().is_empty());
+
+        let mut saved = bracket(0.4, 0.0);
+        saved.stop_loss.order_size = Some(0.6);
+        saved.stop_loss.resized_filled = 0.0;
+        manager.brackets.push(saved.clone());
+        manager.save().await.unwrap();
+
+        let manager = BracketManager::load(client(), SANDBOX_FEED_URL, &path).await.unwrap();
+        assert_eq!(manager.bracket("bracket"), Some(&saved));
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn loads_state_written_before_legs_were_resized() {
+        let path = std::env::temp_dir().join(format!("cbpro-brackets-{}.json", Uuid::new_v4()));
+        let state = serde_json::json!([{
+            "id": "bracket", "product_id": "BTC-USD", "side": "sell", "size": 1.0, "state": "Active",
+            "take_profit": {"client_oid": "a", "order_id": "take-profit", "price": 110.0, "stop_price": null, "filled_size": 0.0, "done": false},
+            "stop_loss": {"client_oid": "b", "order_id": null, "price": 89.0, "stop_price": 90.0, "filled_size": 0.0, "done": false}
+        }]);
+        tokio::fs::write(&path, state.to_string()).await.unwrap();
+
+        let manager = BracketManager::load(client(), SANDBOX_FEED_URL, &path).await.unwrap();
+        let bracket = manager.bracket("bracket").unwrap();
+        assert!(bracket.is_active());
+        assert_eq!(bracket.stop_loss.order_size, None);
+        assert_eq!(bracket.stop_loss.order_id, None);
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
@@ -1,14 +1,69 @@
-use crate::{paging::{Pages, Paginated}, client::Auth};
+use crate::{paging::{Backfill, Direction, Items, Pages, Paginated, Paging, Until}, client::Auth};
 use chrono::{offset::{TimeZone, Utc}, DateTime};
 use hmac::{Hmac, Mac};
 use reqwest::{
     header::{HeaderValue, CONTENT_TYPE},
     Client, Request
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde::de::DeserializeOwned;
 use sha2::Sha256;
-use crate::error::CBError;
+use crate::error::{CBError, Error, Kind};
+use crate::risk::RiskGate;
+
+/// Opaque pagination cursor taken from the `cb-before` and `cb-after` headers.
+/// Depending on the endpoint it holds a trade id, an order id or a timestamp.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cursor(String);
+
+impl Cursor {
+    /// Returns the cursor as sent to the server.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the cursor as a numeric id, fails for non numeric cursors.
+    pub fn as_u64(&self) -> crate::error::Result<u64> {
+        self.0.parse().map_err(|e| Error::new(Kind::Cursor, Some(e)))
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for Cursor {
+    fn from(value: String) -> Self {
+        Cursor(value)
+    }
+}
+
+impl From<&str> for Cursor {
+    fn from(value: &str) -> Self {
+        Cursor(value.to_string())
+    }
+}
+
+impl From<u64> for Cursor {
+    fn from(value: u64) -> Self {
+        Cursor(value.to_string())
+    }
+}
+
+impl From<i64> for Cursor {
+    fn from(value: i64) -> Self {
+        Cursor(value.to_string())
+    }
+}
+
+impl From<i32> for Cursor {
+    fn from(value: i32) -> Self {
+        Cursor(value.to_string())
+    }
+}
 
 #[derive(Serialize)]
 pub struct CBParams<'a> {
@@ -35,8 +90,8 @@ pub struct CBParams<'a> {
     pub(super) funds: Option<f64>,
     //paginate
     limit: Option<i32>,
-    pub(super) before: Option<i32>,
-    after: Option<i32>,
+    pub(super) before: Option<Cursor>,
+    pub(super) after: Option<Cursor>,
     //deposits/withdrawals
     pub(super) amount: Option<f64>,
     pub(super) currency: Option<&'a str>,
@@ -54,6 +109,11 @@ pub struct CBParams<'a> {
     format: Option<&'a str>,
     email: Option<&'a str>,
     pub(super) account_id: Option<&'a str>,
+    //filters
+    pub(super) profile_id: Option<String>,
+    #[serde(rename(serialize = "sortedBy"))]
+    sorted_by: Option<&'a str>,
+    sorting: Option<&'a str>,
 }
 
 impl<'a> CBParams<'a> {
@@ -94,6 +154,9 @@ impl<'a> CBParams<'a> {
             format: None,
             email: None,
             account_id: None,
+            profile_id: None,
+            sorted_by: None,
+            sorting: None,
         }
     }
 }
@@ -109,8 +172,8 @@ pub trait ProductID<'a> {
 
 pub trait Paginate<'a> {
     fn set_limit(&mut self, value: i32);
-    fn set_before(&mut self, value: i32);
-    fn set_after(&mut self, value: i32);
+    fn set_before(&mut self, value: Cursor);
+    fn set_after(&mut self, value: Cursor);
 }
 
 pub trait Book<'a> {
@@ -139,6 +202,20 @@ pub trait Report<'a> {
     fn set_format(&mut self, value: &'a str);
     fn set_email(&mut self, value: &'a str);
 }
+
+pub trait DateRange<'a> {
+    fn set_start_date(&mut self, value: String);
+    fn set_end_date(&mut self, value: String);
+}
+
+pub trait Profile<'a> {
+    fn set_profile_id(&mut self, value: &'a str);
+}
+
+pub trait Sort<'a> {
+    fn set_sorted_by(&mut self, value: &'a str);
+    fn set_sorting(&mut self, value: &'a str);
+}
 //////////////////////////////////////////////////
 
 pub struct NoOptions<'a> {
@@ -191,6 +268,12 @@ impl<'a> ProductID<'a> for CancelOptions<'a> {
     }
 }
 
+impl<'a> Profile<'a> for CancelOptions<'a> {
+    fn set_profile_id(&mut self, value: &'a str) {
+        self.params_mut().profile_id = Some(value.to_string());
+    }
+}
+
 pub struct ListOrderOptions<'a> {
     params: CBParams<'a>,
 }
@@ -223,16 +306,97 @@ impl<'a> Paginate<'a> for ListOrderOptions<'a> {
     fn set_limit(&mut self, value: i32) {
         self.params_mut().limit = Some(value);
     }
-    fn set_before(&mut self, value: i32) {
+    fn set_before(&mut self, value: Cursor) {
+        self.params_mut().before = Some(value);
+        self.params_mut().after = None;
+    }
+    fn set_after(&mut self, value: Cursor) {
+        self.params_mut().after = Some(value);
+        self.params_mut().before = None;
+    }
+}
+
+impl<'a> DateRange<'a> for ListOrderOptions<'a> {
+    fn set_start_date(&mut self, value: String) {
+        self.params_mut().start_date = Some(value);
+    }
+    fn set_end_date(&mut self, value: String) {
+        self.params_mut().end_date = Some(value);
+    }
+}
+
+impl<'a> Profile<'a> for ListOrderOptions<'a> {
+    fn set_profile_id(&mut self, value: &'a str) {
+        self.params_mut().profile_id = Some(value.to_string());
+    }
+}
+
+impl<'a> Sort<'a> for ListOrderOptions<'a> {
+    fn set_sorted_by(&mut self, value: &'a str) {
+        self.params_mut().sorted_by = Some(value);
+    }
+    fn set_sorting(&mut self, value: &'a str) {
+        self.params_mut().sorting = Some(value);
+    }
+}
+
+pub struct FillOptions<'a> {
+    params: CBParams<'a>,
+}
+
+impl<'a> FillOptions<'a> {
+    pub(super) fn new() -> Self {
+        Self {
+            params: CBParams::new()
+        }
+    }
+}
+
+impl<'a> Params<'a> for FillOptions<'a> {
+    fn params_mut(&mut self) -> &mut CBParams<'a> {
+        &mut self.params
+    }
+
+    fn params(&self) -> &CBParams<'a> {
+        &self.params
+    }
+}
+
+impl<'a> ProductID<'a> for FillOptions<'a> {
+    fn set_product_id(&mut self, value: &'a str) {
+        self.params_mut().product_id = Some(value);
+    }
+}
+
+impl<'a> Paginate<'a> for FillOptions<'a> {
+    fn set_limit(&mut self, value: i32) {
+        self.params_mut().limit = Some(value);
+    }
+    fn set_before(&mut self, value: Cursor) {
         self.params_mut().before = Some(value);
         self.params_mut().after = None;
     }
-    fn set_after(&mut self, value: i32) {
+    fn set_after(&mut self, value: Cursor) {
         self.params_mut().after = Some(value);
         self.params_mut().before = None;
     }
 }
 
+impl<'a> DateRange<'a> for FillOptions<'a> {
+    fn set_start_date(&mut self, value: String) {
+        self.params_mut().start_date = Some(value);
+    }
+    fn set_end_date(&mut self, value: String) {
+        self.params_mut().end_date = Some(value);
+    }
+}
+
+impl<'a> Profile<'a> for FillOptions<'a> {
+    fn set_profile_id(&mut self, value: &'a str) {
+        self.params_mut().profile_id = Some(value.to_string());
+    }
+}
+
 pub struct BookOptions<'a> {
     params: CBParams<'a>,
 }
@@ -287,11 +451,104 @@ impl<'a> Paginate<'a> for PageOptions<'a> {
     fn set_limit(&mut self, value: i32) {
         self.params_mut().limit = Some(value);
     }
-    fn set_before(&mut self, value: i32) {
+    fn set_before(&mut self, value: Cursor) {
+        self.params_mut().before = Some(value);
+        self.params_mut().after = None;
+    }
+    fn set_after(&mut self, value: Cursor) {
+        self.params_mut().after = Some(value);
+        self.params_mut().before = None;
+    }
+}
+
+impl<'a> Profile<'a> for PageOptions<'a> {
+    fn set_profile_id(&mut self, value: &'a str) {
+        self.params_mut().profile_id = Some(value.to_string());
+    }
+}
+
+pub struct LedgerOptions<'a> {
+    params: CBParams<'a>,
+}
+
+impl<'a> LedgerOptions<'a> {
+    pub(super) fn new() -> Self {
+        Self {
+            params: CBParams::new()
+        }
+    }
+}
+
+impl<'a> Params<'a> for LedgerOptions<'a> {
+    fn params_mut(&mut self) -> &mut CBParams<'a> {
+        &mut self.params
+    }
+
+    fn params(&self) -> &CBParams<'a> {
+        &self.params
+    }
+}
+
+impl<'a> Paginate<'a> for LedgerOptions<'a> {
+    fn set_limit(&mut self, value: i32) {
+        self.params_mut().limit = Some(value);
+    }
+    fn set_before(&mut self, value: Cursor) {
         self.params_mut().before = Some(value);
         self.params_mut().after = None;
     }
-    fn set_after(&mut self, value: i32) {
+    fn set_after(&mut self, value: Cursor) {
+        self.params_mut().after = Some(value);
+        self.params_mut().before = None;
+    }
+}
+
+impl<'a> DateRange<'a> for LedgerOptions<'a> {
+    fn set_start_date(&mut self, value: String) {
+        self.params_mut().start_date = Some(value);
+    }
+    fn set_end_date(&mut self, value: String) {
+        self.params_mut().end_date = Some(value);
+    }
+}
+
+impl<'a> Profile<'a> for LedgerOptions<'a> {
+    fn set_profile_id(&mut self, value: &'a str) {
+        self.params_mut().profile_id = Some(value.to_string());
+    }
+}
+
+pub struct TradeOptions<'a> {
+    params: CBParams<'a>,
+}
+
+impl<'a> TradeOptions<'a> {
+    pub(super) fn new() -> Self {
+        Self {
+            params: CBParams::new()
+        }
+    }
+}
+
+impl<'a> Params<'a> for TradeOptions<'a> {
+    fn params_mut(&mut self) -> &mut CBParams<'a> {
+        &mut self.params
+    }
+
+    fn params(&self) -> &CBParams<'a> {
+        &self.params
+    }
+}
+
+impl<'a> Paginate<'a> for TradeOptions<'a> {
+    fn set_limit(&mut self, value: i32) {
+        self.params_mut().limit = Some(value);
+    }
+    fn set_before(&mut self, value: Cursor) {
+        self.params_mut().before = Some(value);
+        self.params_mut().after = None;
+    }
+    fn set_after(&mut self, value: Cursor) {
         self.params_mut().after = Some(value);
         self.params_mut().before = None;
     }
@@ -460,6 +717,60 @@ pub(super) fn apply_json<T: Serialize>(req: &mut Request, json: &T) -> crate::er
     Ok(())
 }
 
+pub(super) fn apply_params(request: &mut Request, params: &CBParams) -> crate::error::Result<()> {
+    if let &reqwest::Method::POST = request.method() {
+        apply_json(request, params)?;
+    } else {
+        apply_query(request, params)?;
+    }
+    request.headers_mut().insert("User-Agent", "cbpro".parse().unwrap());
+    Ok(())
+}
+
+pub(super) fn sign_request(request: &mut Request, auth: &Auth) -> crate::error::Result<()> {
+    let Auth { key, pass, secret } = auth;
+
+    let timestamp = Utc::now().timestamp().to_string();
+    let method = request.method().as_str();
+
+    let path = if let Some(query) = request.url().query() {
+        String::new() + request.url().path() + "?" + query
+    } else {
+        request.url().path().to_string()
+    };
+
+    let message = if let Some(body) = request.body() {
+        timestamp.clone()
+            + method
+            + &path[..]
+            + std::str::from_utf8(body.as_bytes().unwrap())?
+    } else {
+        timestamp.clone() + method + &path[..]
+    };
+
+    let hmac_key = base64::decode(secret)?;
+    let mut mac = HmacSha256::new_varkey(&hmac_key)?;
+    mac.input(message.as_bytes());
+    let signature = mac.result().code();
+    let b64_signature = base64::encode(&signature);
+
+    request.headers_mut().insert("CB-ACCESS-KEY", key.parse().unwrap());
+    request.headers_mut().insert("CB-ACCESS-PASSPHRASE", pass.parse().unwrap());
+    request.headers_mut().insert("CB-ACCESS-TIMESTAMP", (&timestamp[..]).parse().unwrap());
+    request.headers_mut().insert("CB-ACCESS-SIGN", (&b64_signature[..]).parse().unwrap());
+    Ok(())
+}
+
+pub(super) fn prepare_request(request: &Request, params: &CBParams, auth: Option<&Auth>) -> crate::error::Result<Request> {
+    let mut request = request.try_clone().unwrap();
+    apply_params(&mut request, params)?;
+
+    if let Some(auth) = auth {
+        sign_request(&mut request, auth)?;
+    }
+    Ok(request)
+}
+
 /// Builder returned by the public and private client. 
 /// All methods are optional but the builder must be consumed with one of the terminal methods.
 /// Methods belonging to this struct can be chained and calling the same method more than once will overwrite the previously set value.
@@ -468,6 +779,9 @@ pub struct QueryBuilder<T> {
     request: Request,
     query: T,
     auth: Option<Auth>,
+    paging: Paging,
+    error: Option<Error>,
+    risk: Option<RiskGate>,
 }
 
 impl<'a, T: Params<'a>> QueryBuilder<T> {
@@ -482,56 +796,44 @@ impl<'a, T: Params<'a>> QueryBuilder<T> {
             request,
             query,
             auth,
+            paging: Paging::default(),
+            error: None,
+            risk: None,
         }
     }
 
-    fn auth_request(&self) -> crate::error::Result<Request> {
-        let mut request = self.request.try_clone().unwrap();
-
-        if let &reqwest::Method::POST = request.method() {
-            apply_json(&mut request, self.query.params())?;
-        } else {
-            apply_query(&mut request, self.query.params())?;
-        }
-
-        if let Some(ref auth) = self.auth {
-            let Auth { key, pass, secret } = auth;
-
-            let timestamp = Utc::now().timestamp().to_string();
-            let method = request.method().as_str();
+    /// Makes every terminal method return the error without sending the request.
+    pub(super) fn fail(mut self, error: Error) -> Self {
+        self.error = Some(error);
+        self
+    }
 
-            let path = if let Some(query) = request.url().query() {
-                String::new() + request.url().path() + "?" + query
-            } else {
-                request.url().path().to_string()
-            };
-
-            let message = if let Some(body) = request.body() {
-                timestamp.clone()
-                    + method
-                    + &path[..]
-                    + std::str::from_utf8(body.as_bytes().unwrap())?
-            } else {
-                timestamp.clone() + method + &path[..]
-            };
-
-            let hmac_key = base64::decode(secret)?;
-            let mut mac = HmacSha256::new_varkey(&hmac_key)?;
-            mac.input(message.as_bytes());
-            let signature = mac.result().code();
-            let b64_signature = base64::encode(&signature);
-
-            request.headers_mut().insert("CB-ACCESS-KEY", key.parse().unwrap());
-            request.headers_mut().insert("CB-ACCESS-PASSPHRASE", pass.parse().unwrap());
-            request.headers_mut().insert("CB-ACCESS-TIMESTAMP", (&timestamp[..]).parse().unwrap());
-            request.headers_mut().insert("CB-ACCESS-SIGN", (&b64_signature[..]).parse().unwrap());
-        }
+    /// Checks the order against the gate right before it is sent.
+    pub(super) fn risk_gate(mut self, gate: Option<RiskGate>) -> Self {
+        self.risk = gate;
+        self
+    }
 
-        request.headers_mut().insert("User-Agent", "cbpro".parse().unwrap());
-        Ok(request)
+    fn auth_request(&self) -> crate::error::Result<Request> {
+        prepare_request(&self.request, self.query.params(), self.auth.as_ref())
     }
     
     pub async fn text(self) -> crate::error::Result<String> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let gate = match self.risk {
+            Some(ref gate) => Some((gate, gate.reserve(self.query.params())?)),
+            None => None,
+        };
+        let result = self.send().await;
+        if let Some((gate, ticket)) = gate {
+            gate.settle(ticket, result.as_ref().ok().map(String::as_str));
+        }
+        result
+    }
+
+    async fn send(&self) -> crate::error::Result<String> {
         let resp = self.client.execute(self.auth_request()?).await?;
         if resp.status().is_success() {
             Ok(resp.text().await?)
@@ -640,23 +942,123 @@ impl<'a, T: Params<'a> + Paginate<'a> + Send + Unpin + 'a> QueryBuilder<T> {
         self
     }
 
-    /// Gets newer page from the trade id specified.
-    pub fn before(mut self, value: i32) -> Self {
-        self.query.set_before(value);
+    /// Gets newer page from the cursor specified.
+    pub fn before<C: Into<Cursor>>(mut self, value: C) -> Self {
+        self.query.set_before(value.into());
+        self
+    }
+
+    /// Gets older page from the cursor specified.
+    pub fn after<C: Into<Cursor>>(mut self, value: C) -> Self {
+        self.query.set_after(value.into());
+        self
+    }
+    /// Sets which cursor is followed between pages.
+    /// Defaults to newer if `before` is set and older otherwise.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{PublicClient, SANDBOX_URL};
+    /// # use futures::TryStreamExt;
+    /// use cbpro::Direction;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = PublicClient::new(SANDBOX_URL);
+    /// let mut pages = client.get_trades("BTC-USD")
+    ///     .before(7102310)
+    ///     .direction(Direction::Newer)
+    ///     .tail(core::time::Duration::from_secs(5))
+    ///     .paginate::<serde_json::Value>()?;
+    ///
+    /// while let Some(json) = pages.try_next().await? {
+    ///     println!("{}", serde_json::to_string_pretty(&json).unwrap());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn direction(mut self, value: Direction) -> Self {
+        self.paging.direction = Some(value);
         self
     }
 
-    /// Gets older page from the trade id specified.
-    pub fn after(mut self, value: i32) -> Self {
-        self.query.set_after(value);
+    /// Keeps polling for newer pages at the given interval once caught up instead of ending the stream.
+    /// Only applies when paginating in the newer direction.
+    pub fn tail(mut self, interval: core::time::Duration) -> Self {
+        self.paging.tail = Some(interval);
         self
     }
+
     /// Terminal method returning a stream of json pages
     pub fn paginate<J: DeserializeOwned>(self) -> crate::error::Result<Pages<'a, J>> {
-        let pages = Paginated::new(self.client.clone(), self.auth_request()?, self.query).pages();
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.auth_request()?;
+        let pages = Paginated::new(self.client, self.request, self.query, self.auth, self.paging).pages();
         Ok(pages)
     }
+    /// Terminal method returning a stream of items flattened from json array pages
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{PublicClient, SANDBOX_URL};
+    /// # use futures::TryStreamExt;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = PublicClient::new(SANDBOX_URL);
+    /// let mut trades = client.get_trades("BTC-USD")
+    ///     .limit(100)
+    ///     .paginate_items::<serde_json::Value>()?
+    ///     .max_pages(10);
+    ///
+    /// while let Some(trade) = trades.try_next().await? {
+    ///     println!("{}", serde_json::to_string_pretty(&trade).unwrap());
+    /// }
+    /// println!("resume with after({:?})", trades.after());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn paginate_items<J: DeserializeOwned + Send + 'static>(self) -> crate::error::Result<Items<'a, J>> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.auth_request()?;
+        Ok(Items::new(self.client, self.request, self.query, self.auth, self.paging))
+    }
 }
+impl<'a> QueryBuilder<TradeOptions<'a>> {
+    /// Terminal method returning a stream of trades walking backward until the target is reached.
+    /// Use [Backfill::cursor](../struct.Backfill.html#method.cursor) with `after` to resume an interrupted backfill.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{PublicClient, SANDBOX_URL};
+    /// use cbpro::Until;
+    /// use futures::TryStreamExt;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = PublicClient::new(SANDBOX_URL);
+    /// let start = chrono::offset::Utc::now() - chrono::Duration::days(1);
+    /// let mut trades = client.backfill_trades("BTC-USD")
+    ///     .limit(100)
+    ///     .backfill(Until::Time(start));
+    ///
+    /// while let Some(trade) = trades.try_next().await? {
+    ///     println!("{:?}", trade);
+    ///     // persist trades.cursor() to resume later with .after(cursor)
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn backfill(self, until: Until) -> Backfill<'a> {
+        let backfill = Backfill::new(self.client, self.request, self.query, self.auth, until);
+        match self.error {
+            Some(error) => backfill.fail(error),
+            None => backfill,
+        }
+    }
+}
+
 impl<'a, T: Params<'a> + Candle<'a>> QueryBuilder<T> {
     /// Sets start and end time for historic rates.
     /// If the range results in more than 300 candles, the request will be rejected.
@@ -895,4 +1297,102 @@ impl<'a, T: Params<'a> + Report<'a>> QueryBuilder<T> {
         self.query.set_email(value);
         self
     }
+}
+
+impl<'a, T: Params<'a> + DateRange<'a>> QueryBuilder<T> {
+    /// Only returns results created at or after the given time.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL, FILL};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let end = chrono::offset::Utc::now();
+    /// let start = end - chrono::Duration::days(7);
+    ///
+    /// let fills = client
+    ///     .get_fills(FILL::ProductID("BTC-USD"))
+    ///     .start_date(start)
+    ///     .end_date(end)
+    ///     .json::<serde_json::Value>()
+    ///     .await?;
+    /// 
+    /// println!("{}", serde_json::to_string_pretty(&fills).unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn start_date<Tz: TimeZone>(mut self, value: DateTime<Tz>) -> Self
+    where
+        Tz::Offset: core::fmt::Display,
+    {
+        self.query.set_start_date(value.to_rfc3339());
+        self
+    }
+    /// Only returns results created at or before the given time.
+    pub fn end_date<Tz: TimeZone>(mut self, value: DateTime<Tz>) -> Self
+    where
+        Tz::Offset: core::fmt::Display,
+    {
+        self.query.set_end_date(value.to_rfc3339());
+        self
+    }
+}
+
+impl<'a, T: Params<'a> + Profile<'a>> QueryBuilder<T> {
+    /// Scopes the request to a profile the API key has access to.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let response = client
+    ///     .cancel_all()
+    ///     .profile_id("<profile_id>")
+    ///     .json::<serde_json::Value>()
+    ///     .await?;
+    /// 
+    /// println!("{}", serde_json::to_string_pretty(&response).unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn profile_id(mut self, value: &'a str) -> Self {
+        self.query.set_profile_id(value);
+        self
+    }
+}
+
+impl<'a, T: Params<'a> + Sort<'a>> QueryBuilder<T> {
+    /// Sets the field results are sorted by.
+    /// Valid inputs are: "created_at", "price", "size", "order_id", "side", "type".
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let orders = client
+    ///     .list_orders(&["done"])
+    ///     .sorted_by("price")
+    ///     .sorting("asc")
+    ///     .json::<serde_json::Value>()
+    ///     .await?;
+    /// 
+    /// println!("{}", serde_json::to_string_pretty(&orders).unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sorted_by(mut self, value: &'a str) -> Self {
+        self.query.set_sorted_by(value);
+        self
+    }
+    /// Sets the sort order.
+    /// Valid inputs are: "asc", "desc" (default is desc)
+    pub fn sorting(mut self, value: &'a str) -> Self {
+        self.query.set_sorting(value);
+        self
+    }
 }
\ No newline at end of file
@@ -0,0 +1,460 @@
+use core::pin::Pin;
+use std::collections::VecDeque;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use futures::{
+    stream::{Stream, StreamExt},
+    task::{Context, Poll},
+};
+use serde::Serialize;
+use crate::models::Trade;
+
+/// Rule used to close a bar
+#[derive(Debug, Clone, Copy)]
+pub enum BarType {
+    /// Fixed time interval aligned to the builder origin, e.g. `Duration::minutes(2)` or `Duration::weeks(1)`
+    Time(Duration),
+    /// Closes once the traded base volume reaches the threshold
+    Volume(f64),
+    /// Closes after the given number of trades
+    Tick(u64),
+    /// Closes once the traded quote value (price * size) reaches the threshold
+    Dollar(f64),
+}
+
+/// OHLCV bar
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Candle {
+    /// Bucket start for time bars, time of the first trade otherwise
+    pub start: DateTime<Utc>,
+    /// Bucket end for time bars, time of the last trade otherwise
+    pub end: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Base currency volume
+    pub volume: f64,
+    /// Quote currency volume
+    pub value: f64,
+    pub trades: u64,
+    pub first_trade_id: u64,
+    pub last_trade_id: u64,
+}
+
+impl Candle {
+    fn open(trade: &Trade, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            end,
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: trade.size,
+            value: trade.price * trade.size,
+            trades: 1,
+            first_trade_id: trade.trade_id,
+            last_trade_id: trade.trade_id,
+        }
+    }
+
+    fn update(&mut self, trade: &Trade) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.close = trade.price;
+        self.volume += trade.size;
+        self.value += trade.price * trade.size;
+        self.trades += 1;
+        self.last_trade_id = trade.trade_id;
+    }
+}
+
+/// Bar emitted by the [CandleBuilder](struct.CandleBuilder.html)
+#[derive(Debug, Clone, PartialEq)]
+pub enum CandleEvent {
+    /// Bar that is still open and may change with the next trade
+    Partial(Candle),
+    /// Bar that is closed and will not change anymore
+    Final(Candle),
+}
+
+/// Aggregates trades into bars.
+/// Trades must be pushed oldest first, pages from `get_trades` are newest first and need to be reversed.
+/// For time bars, trades older than the bar being built or the last closed bar are dropped.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::candles::{BarType, CandleBuilder, CandleEvent};
+/// use cbpro::models::Trade;
+/// use cbpro::websocket::{Channels, WebSocketFeed, SANDBOX_FEED_URL};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut feed = WebSocketFeed::connect(SANDBOX_FEED_URL).await?;
+/// feed.subscribe(&["BTC-USD"], &[Channels::MATCHES]).await?;
+///
+/// let mut builder = CandleBuilder::new(BarType::Time(chrono::Duration::minutes(2)));
+/// while let Some(value) = feed.json::<serde_json::Value>().await? {
+///     if value["type"] != "match" {
+///         continue;
+///     }
+///     let trade: Trade = serde_json::from_value(value)?;
+///     for event in builder.push(&trade) {
+///         if let CandleEvent::Final(candle) = event {
+///             println!("{:?}", candle);
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct CandleBuilder {
+    bar: BarType,
+    origin: DateTime<Utc>,
+    current: Option<Candle>,
+    closed: Option<DateTime<Utc>>,
+}
+
+impl CandleBuilder {
+    /// Creates new builder, time bars are aligned to the unix epoch by default.
+    pub fn new(bar: BarType) -> Self {
+        if let BarType::Time(interval) = bar {
+            assert!(interval > Duration::zero(), "Time bar interval must be positive");
+        }
+        Self {
+            bar,
+            origin: Utc.timestamp(0, 0),
+            current: None,
+            closed: None,
+        }
+    }
+
+    /// Sets the time bars are aligned to, e.g. a monday for weekly bars.
+    pub fn origin(mut self, origin: DateTime<Utc>) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Returns the bar currently being built.
+    pub fn current(&self) -> Option<&Candle> {
+        self.current.as_ref()
+    }
+
+    /// Closes and returns the bar currently being built.
+    pub fn finish(&mut self) -> Option<Candle> {
+        let candle = self.current.take()?;
+        if let BarType::Time(_) = self.bar {
+            self.closed = Some(candle.end);
+        }
+        Some(candle)
+    }
+
+    /// Adds a trade and returns the bars it closed or updated.
+    /// Returns no events when the trade is dropped.
+    pub fn push(&mut self, trade: &Trade) -> Vec<CandleEvent> {
+        let mut events = Vec::with_capacity(2);
+
+        if let BarType::Time(interval) = self.bar {
+            let stale = match (&self.current, self.closed) {
+                (Some(candle), _) => trade.time < candle.start,
+                (None, Some(closed)) => trade.time < closed,
+                (None, None) => false,
+            };
+            if stale {
+                return events;
+            }
+
+            if let Some(candle) = self.current.take() {
+                if trade.time >= candle.end {
+                    self.closed = Some(candle.end);
+                    events.push(CandleEvent::Final(candle));
+                } else {
+                    self.current = Some(candle);
+                }
+            }
+
+            match self.current {
+                Some(ref mut candle) => candle.update(trade),
+                None => {
+                    let step = interval.num_milliseconds();
+                    let elapsed = (trade.time - self.origin).num_milliseconds();
+                    let start = self.origin + Duration::milliseconds(elapsed.div_euclid(step) * step);
+                    self.current = Some(Candle::open(trade, start, start + interval));
+                }
+            }
+            events.push(CandleEvent::Partial(self.current.clone().unwrap()));
+            return events;
+        }
+
+        match self.current {
+            Some(ref mut candle) => {
+                candle.update(trade);
+                candle.end = candle.end.max(trade.time);
+            }
+            None => self.current = Some(Candle::open(trade, trade.time, trade.time)),
+        }
+
+        let candle = self.current.as_ref().unwrap();
+        let closed = match self.bar {
+            BarType::Volume(threshold) => candle.volume >= threshold,
+            BarType::Tick(threshold) => candle.trades >= threshold,
+            BarType::Dollar(threshold) => candle.value >= threshold,
+            BarType::Time(_) => unreachable!(),
+        };
+
+        if closed {
+            events.push(CandleEvent::Final(self.current.take().unwrap()));
+        } else {
+            events.push(CandleEvent::Partial(candle.clone()));
+        }
+        events
+    }
+
+    /// Turns a stream of trades into a stream of bars.
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cbpro::candles::{BarType, CandleBuilder};
+    /// use cbpro::client::{PublicClient, SANDBOX_URL};
+    /// use cbpro::models::Trade;
+    /// use futures::{stream, TryStreamExt};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = PublicClient::new(SANDBOX_URL);
+    /// let mut trades = client
+    ///     .get_trades("BTC-USD")
+    ///     .json::<Vec<Trade>>()
+    ///     .await?;
+    /// trades.reverse();
+    ///
+    /// let mut candles = CandleBuilder::new(BarType::Volume(10.0))
+    ///     .stream(stream::iter(trades.into_iter().map(Ok)));
+    ///
+    /// while let Some(event) = candles.try_next().await? {
+    ///     println!("{:?}", event);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stream<S>(self, trades: S) -> Candles<S>
+    where
+        S: Stream<Item = crate::error::Result<Trade>> + Unpin,
+    {
+        Candles {
+            trades,
+            builder: self,
+            events: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+/// Stream of bars returned by [CandleBuilder::stream](struct.CandleBuilder.html#method.stream).
+/// The open bar is emitted as final once the trade stream ends.
+pub struct Candles<S> {
+    trades: S,
+    builder: CandleBuilder,
+    events: VecDeque<CandleEvent>,
+    done: bool,
+}
+
+impl<S> Candles<S> {
+    /// Returns a shared reference to the underlying builder.
+    pub fn builder(&self) -> &CandleBuilder {
+        &self.builder
+    }
+}
+
+impl<S> Stream for Candles<S>
+where
+    S: Stream<Item = crate::error::Result<Trade>> + Unpin,
+{
+    type Item = crate::error::Result<CandleEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+
+            if self.done {
+                return Poll::Ready(None);
+            }
+
+            match self.trades.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(trade))) => {
+                    let events = self.builder.push(&trade);
+                    self.events.extend(events);
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => {
+                    self.done = true;
+                    if let Some(candle) = self.builder.finish() {
+                        self.events.push_back(CandleEvent::Final(candle));
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(trade_id: u64, secs: i64, price: f64, size: f64) -> Trade {
+        Trade {
+            trade_id,
+            time: Utc.timestamp(secs, 0),
+            price,
+            size,
+            side: "buy".to_string(),
+            product_id: None,
+        }
+    }
+
+    fn finals(events: &[CandleEvent]) -> Vec<&Candle> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                CandleEvent::Final(candle) => Some(candle),
+                CandleEvent::Partial(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn time_bars_close_on_bucket_boundary() {
+        let mut builder = CandleBuilder::new(BarType::Time(Duration::seconds(60)));
+
+        let events = builder.push(&trade(1, 61, 10.0, 1.0));
+        assert_eq!(events.len(), 1);
+        let candle = builder.current().unwrap();
+        assert_eq!(candle.start, Utc.timestamp(60, 0));
+        assert_eq!(candle.end, Utc.timestamp(120, 0));
+
+        builder.push(&trade(2, 90, 12.0, 2.0));
+        builder.push(&trade(3, 119, 9.0, 1.0));
+
+        let events = builder.push(&trade(4, 120, 11.0, 1.0));
+        let closed = finals(&events);
+        assert_eq!(closed.len(), 1);
+        let candle = closed[0];
+        assert_eq!((candle.open, candle.high, candle.low, candle.close), (10.0, 12.0, 9.0, 9.0));
+        assert_eq!(candle.volume, 4.0);
+        assert_eq!(candle.value, 43.0);
+        assert_eq!(candle.trades, 3);
+        assert_eq!((candle.first_trade_id, candle.last_trade_id), (1, 3));
+        assert_eq!(events.last(), Some(&CandleEvent::Partial(builder.current().unwrap().clone())));
+        assert_eq!(builder.current().unwrap().start, Utc.timestamp(120, 0));
+    }
+
+    #[test]
+    fn time_bars_skip_empty_buckets_and_respect_origin() {
+        let mut builder = CandleBuilder::new(BarType::Time(Duration::seconds(60))).origin(Utc.timestamp(30, 0));
+
+        builder.push(&trade(1, 40, 10.0, 1.0));
+        assert_eq!(builder.current().unwrap().start, Utc.timestamp(30, 0));
+
+        let events = builder.push(&trade(2, 400, 11.0, 1.0));
+        assert_eq!(finals(&events)[0].end, Utc.timestamp(90, 0));
+        assert_eq!(builder.current().unwrap().start, Utc.timestamp(390, 0));
+    }
+
+    #[test]
+    fn time_bars_drop_out_of_order_trades() {
+        let mut builder = CandleBuilder::new(BarType::Time(Duration::seconds(60)));
+
+        builder.push(&trade(1, 70, 10.0, 1.0));
+        assert!(builder.push(&trade(0, 50, 99.0, 5.0)).is_empty());
+        let candle = builder.current().unwrap();
+        assert_eq!((candle.high, candle.volume, candle.trades), (10.0, 1.0, 1));
+
+        builder.push(&trade(2, 65, 11.0, 1.0));
+        assert_eq!(builder.current().unwrap().trades, 2);
+
+        builder.push(&trade(3, 130, 12.0, 1.0));
+        assert!(builder.push(&trade(4, 100, 99.0, 5.0)).is_empty());
+        assert_eq!(builder.current().unwrap().trades, 1);
+
+        let candle = builder.finish().unwrap();
+        assert_eq!(candle.end, Utc.timestamp(180, 0));
+        assert!(builder.push(&trade(5, 170, 99.0, 5.0)).is_empty());
+        assert!(builder.current().is_none());
+    }
+
+    #[test]
+    fn tick_bars_close_after_count() {
+        let mut builder = CandleBuilder::new(BarType::Tick(3));
+
+        assert!(finals(&builder.push(&trade(1, 1, 10.0, 1.0))).is_empty());
+        assert!(finals(&builder.push(&trade(2, 2, 11.0, 1.0))).is_empty());
+        let events = builder.push(&trade(3, 3, 9.0, 1.0));
+        assert_eq!(events.len(), 1);
+        let candle = finals(&events)[0];
+        assert_eq!(candle.trades, 3);
+        assert_eq!((candle.start, candle.end), (Utc.timestamp(1, 0), Utc.timestamp(3, 0)));
+        assert!(builder.current().is_none());
+
+        builder.push(&trade(4, 4, 10.0, 1.0));
+        assert_eq!(builder.current().unwrap().first_trade_id, 4);
+    }
+
+    #[test]
+    fn tick_bars_keep_end_on_out_of_order_trade() {
+        let mut builder = CandleBuilder::new(BarType::Tick(3));
+
+        builder.push(&trade(2, 10, 10.0, 1.0));
+        builder.push(&trade(1, 5, 11.0, 1.0));
+        let candle = builder.current().unwrap();
+        assert_eq!((candle.start, candle.end), (Utc.timestamp(10, 0), Utc.timestamp(10, 0)));
+    }
+
+    #[test]
+    fn volume_bars_close_at_threshold() {
+        let mut builder = CandleBuilder::new(BarType::Volume(2.0));
+
+        assert!(finals(&builder.push(&trade(1, 1, 10.0, 1.5))).is_empty());
+        let events = builder.push(&trade(2, 2, 10.0, 0.5));
+        let candle = finals(&events)[0];
+        assert_eq!(candle.volume, 2.0);
+        assert_eq!(candle.trades, 2);
+
+        let events = builder.push(&trade(3, 3, 10.0, 5.0));
+        assert_eq!(finals(&events)[0].volume, 5.0);
+        assert!(builder.current().is_none());
+    }
+
+    #[test]
+    fn dollar_bars_close_at_threshold() {
+        let mut builder = CandleBuilder::new(BarType::Dollar(100.0));
+
+        assert!(finals(&builder.push(&trade(1, 1, 20.0, 2.0))).is_empty());
+        assert!(finals(&builder.push(&trade(2, 2, 25.0, 2.0))).is_empty());
+        let events = builder.push(&trade(3, 3, 10.0, 1.0));
+        let candle = finals(&events)[0];
+        assert_eq!(candle.value, 100.0);
+        assert_eq!(candle.volume, 5.0);
+        assert_eq!(candle.close, 10.0);
+    }
+
+    #[tokio::test]
+    async fn stream_closes_open_bar_at_end() {
+        use futures::{stream, TryStreamExt};
+
+        let trades = vec![trade(1, 1, 10.0, 1.0), trade(2, 61, 11.0, 1.0)];
+        let events: Vec<CandleEvent> = CandleBuilder::new(BarType::Time(Duration::seconds(60)))
+            .stream(stream::iter(trades.into_iter().map(Ok)))
+            .try_collect()
+            .await
+            .unwrap();
+
+        let closed = finals(&events);
+        assert_eq!(closed.len(), 2);
+        assert_eq!(closed[0].start, Utc.timestamp(0, 0));
+        assert_eq!(closed[1].start, Utc.timestamp(60, 0));
+        assert!(matches!(events.last(), Some(CandleEvent::Final(_))));
+    }
+}
@@ -1,13 +1,22 @@
 use reqwest::{ Client, Url };
 use chrono::{offset::TimeZone, DateTime};
 use crate::builder::*;
+use crate::error::{Error, Kind};
+use crate::models::{Account, Profile, ProfileAccounts};
+use crate::batch::{Batch, OrderRequest};
+use crate::replace::ReplaceOrder;
+use crate::risk::RiskGate;
+use crate::reports::ReportRequest;
 
 /// https://api-public.sandbox.pro.coinbase.com
 pub const SANDBOX_URL: &'static str = "https://api-public.sandbox.pro.coinbase.com";
 /// https://api.pro.coinbase.com
 pub const MAIN_URL: &'static str = "https://api.pro.coinbase.com";
+/// Currency pairs supported by [convert](struct.AuthenticatedClient.html#method.convert)
+pub const CONVERSIONS: &[(&str, &str)] = &[("USD", "USDC"), ("USDC", "USD")];
 
 /// ID variants for orders
+#[derive(Debug, Clone, Copy)]
 pub enum ORD<'a> {
     /// order_id param
     OrderID(&'a str),
@@ -50,6 +59,7 @@ pub enum RPT<'a> {
     Account { account_id: &'a str }
 }
 /// Quantity variants for market orders                                                                                                                                                                                                                                                              
+#[derive(Debug, Clone, Copy)]
 pub enum QTY {
     /// Quantity to buy or sell
     Size(f64),
@@ -65,9 +75,12 @@ pub(super) struct Auth {
 }
 
 /// Private client
+#[derive(Clone)]
 pub struct AuthenticatedClient {
     auth: Auth,
     public: PublicClient,
+    profile_id: Option<String>,
+    risk: Option<RiskGate>,
 }
 
 impl AuthenticatedClient {
@@ -93,13 +106,19 @@ impl AuthenticatedClient {
         Self {
             auth: Auth { key: key.into(), pass: pass.into(), secret: secret.into() },
             public: PublicClient::new(url),
+            profile_id: None,
+            risk: None,
         }
     }
 
-    fn client(&self) -> &Client {
+    pub(super) fn client(&self) -> &Client {
         &self.public.client
     }
 
+    pub(super) fn auth(&self) -> &Auth {
+        &self.auth
+    }
+
     fn url(&self) -> &Url {
         &self.public.url
     }
@@ -107,6 +126,103 @@ impl AuthenticatedClient {
     pub fn public(&self) -> &PublicClient {
         &self.public
     }
+
+    fn scoped<'a, T: Params<'a>>(&self, mut options: T) -> T {
+        if let Some(ref profile_id) = self.profile_id {
+            options.params_mut().profile_id = Some(profile_id.clone());
+        }
+        options
+    }
+    /// Returns a client which scopes orders, accounts, fills, transfers, conversions and reports 
+    /// to the given profile, for API keys that have access to several profiles.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let orders = client
+    ///     .with_profile("<profile_id>")
+    ///     .list_orders(&["open"])
+    ///     .json::<serde_json::Value>()
+    ///     .await?;
+    /// 
+    /// println!("{}", serde_json::to_string_pretty(&orders).unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_profile<P: Into<String>>(&self, profile_id: P) -> AuthenticatedClient {
+        Self {
+            profile_id: Some(profile_id.into()),
+            ..self.clone()
+        }
+    }
+    /// Returns a client which checks every limit and market order against the gate before placing it.
+    /// # Example
+    ///
+    /// ```
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// use cbpro::risk::{RiskGate, RiskViolation};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let gate = RiskGate::new().max_notional(1000.00);
+    /// let result = client
+    ///     .with_risk_gate(gate)
+    ///     .place_limit_order("BTC-USD", "buy", 7000.00, 1.0)
+    ///     .json::<serde_json::Value>()
+    ///     .await;
+    ///
+    /// assert!(matches!(result.unwrap_err().risk(), Some(RiskViolation::MaxNotional { .. })));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_risk_gate(&self, gate: RiskGate) -> AuthenticatedClient {
+        Self {
+            risk: Some(gate),
+            ..self.clone()
+        }
+    }
+    /// Returns the risk gate orders of this client are checked against.
+    pub fn risk_gate(&self) -> Option<&RiskGate> {
+        self.risk.as_ref()
+    }
+    /// Returns the profile this client is scoped to.
+    pub fn profile_id(&self) -> Option<&str> {
+        self.profile_id.as_deref()
+    }
+    /// Lists the accounts and balances of every profile the API key has access to.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// for entry in client.list_all_accounts().await? {
+    ///     for account in entry.accounts {
+    ///         println!("{} {} {}", entry.profile.name, account.currency, account.balance);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn list_all_accounts(&self) -> crate::error::Result<Vec<ProfileAccounts>> {
+        let profiles = self.list_profiles().json::<Vec<Profile>>().await?;
+        let mut all = Vec::with_capacity(profiles.len());
+
+        for profile in profiles {
+            let accounts = self
+                .with_profile(profile.id.as_str())
+                .list_accounts()
+                .json::<Vec<Account>>()
+                .await?;
+            all.push(ProfileAccounts { profile, accounts });
+        }
+        Ok(all)
+    }
     /// Get a list of trading accounts from the profile of the API key.
     /// # Example
     ///
@@ -129,7 +245,7 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().get(url).build().unwrap(),
-            NoOptions::new(),
+            self.scoped(NoOptions::new()),
             Some(self.auth.clone()),
         )
     }
@@ -158,7 +274,7 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().get(url).build().unwrap(),
-            NoOptions::new(),
+            self.scoped(NoOptions::new()),
             Some(self.auth.clone()),
         )
     }
@@ -181,13 +297,13 @@ impl AuthenticatedClient {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_account_history<'a>(&self, account_id: &str) -> QueryBuilder<PageOptions<'a>> {
+    pub fn get_account_history<'a>(&self, account_id: &str) -> QueryBuilder<LedgerOptions<'a>> {
         let endpoint = format!("/accounts/{}/ledger", account_id);
         let url = self.url().join(&endpoint).unwrap();
         QueryBuilder::new(
             self.client().clone(),
             self.client().get(url).build().unwrap(),
-            PageOptions::new(),
+            self.scoped(LedgerOptions::new()),
             Some(self.auth.clone()),
         )
     }
@@ -217,7 +333,7 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().get(url).build().unwrap(),
-            PageOptions::new(),
+            self.scoped(PageOptions::new()),
             Some(self.auth.clone()),
         )
     }
@@ -252,9 +368,10 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().post(url).build().unwrap(),
-            limit_options,
+            self.scoped(limit_options),
             Some(self.auth.clone()),
         )
+        .risk_gate(self.risk.clone())
     }
     /// Orders can only be placed if your account has sufficient funds. 
     /// Once an order is placed, your account funds will be put on hold for the duration of the order. 
@@ -290,9 +407,10 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().post(url).build().unwrap(),
-            market_options,
+            self.scoped(market_options),
             Some(self.auth.clone()),
         )
+        .risk_gate(self.risk.clone())
     }
     /// Cancel a previously placed order. 
     /// Order must belong to the profile that the API key belongs to.
@@ -324,10 +442,93 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().delete(url).build().unwrap(),
-            NoOptions::new(),
+            self.scoped(NoOptions::new()),
             Some(self.auth.clone()),
         )
     }
+    /// Places several orders with bounded concurrency, each result is paired with its request.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// use cbpro::batch::OrderRequest;
+    /// use cbpro::models::Order;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let ladder = (0..50)
+    ///     .map(|step| OrderRequest::limit("BTC-USD", "buy", 7000.00 - step as f64 * 10.0, 0.01).post_only(true))
+    ///     .collect();
+    ///
+    /// let results = client
+    ///     .place_orders(ladder)
+    ///     .concurrency(4)
+    ///     .json::<Order>()
+    ///     .await;
+    ///
+    /// for (request, result) in results {
+    ///     match result {
+    ///         Ok(order) => println!("{:?} placed as {}", request.kind, order.id),
+    ///         Err(error) => println!("{:?} failed: {}", request.kind, error),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn place_orders(&self, orders: Vec<OrderRequest>) -> Batch<OrderRequest> {
+        Batch::new(self.clone(), orders)
+    }
+    /// Cancels several orders with bounded concurrency, each result is paired with its id.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL, ORD};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let results = client
+    ///     .cancel_orders(vec![ORD::OrderID("<order_id>"), ORD::ClientOID("<client_oid>")])
+    ///     .json::<serde_json::Value>()
+    ///     .await;
+    ///
+    /// for (ord, result) in results {
+    ///     println!("{:?}: {:?}", ord, result.is_ok());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cancel_orders<'a>(&self, ords: Vec<ORD<'a>>) -> Batch<ORD<'a>> {
+        Batch::new(self.clone(), ords)
+    }
+    /// Cancels an order and, once the cancel is confirmed with `get_order`, places the replacement.
+    /// Nothing is placed when the order filled in the meantime.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL, ORD};
+    /// use cbpro::batch::OrderRequest;
+    /// use cbpro::replace::ReplaceOutcome;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let outcome = client
+    ///     .replace_order(ORD::OrderID("<order_id>"), OrderRequest::limit("BTC-USD", "buy", 7100.00, 1.0))
+    ///     .reduce_by_filled(true)
+    ///     .send()
+    ///     .await?;
+    ///
+    /// match outcome {
+    ///     ReplaceOutcome::Filled { order } => println!("filled at {:?}", order.price),
+    ///     outcome => println!("{:?}", outcome.placed()),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn replace_order<'a>(&self, ord: ORD<'a>, request: OrderRequest) -> ReplaceOrder<'a> {
+        ReplaceOrder::new(self.clone(), ord, request)
+    }
     /// With best effort, cancel all open orders from the profile that the API key belongs to. 
     /// The response is a list of ids of the canceled orders.
     /// # Example
@@ -351,7 +552,7 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().delete(url).build().unwrap(),
-            CancelOptions::new(),
+            self.scoped(CancelOptions::new()),
             Some(self.auth.clone()),
         )
     }
@@ -380,7 +581,7 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().get(url).query(&status).build().unwrap(),
-            ListOrderOptions::new(),
+            self.scoped(ListOrderOptions::new()),
             Some(self.auth.clone()),
         )
     }
@@ -412,7 +613,7 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().get(url).build().unwrap(),
-            NoOptions::new(),
+            self.scoped(NoOptions::new()),
             Some(self.auth.clone()),
         )
     }
@@ -435,19 +636,19 @@ impl AuthenticatedClient {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_fills<'a>(&self, fill: FILL<'a>) -> QueryBuilder<NoOptions<'a>> {
+    pub fn get_fills<'a>(&self, fill: FILL<'a>) -> QueryBuilder<FillOptions<'a>> {
         let url = self.url().join("/fills").unwrap();
 
-        let mut no_options = NoOptions::new();
+        let mut fill_options = FillOptions::new();
         match fill {
-            FILL::OrderID(id) => no_options.params_mut().order_id = Some(id),
-            FILL::ProductID(id) => no_options.params_mut().product_id = Some(id)
+            FILL::OrderID(id) => fill_options.params_mut().order_id = Some(id),
+            FILL::ProductID(id) => fill_options.params_mut().product_id = Some(id)
         }
 
         QueryBuilder::new(
             self.client().clone(),
             self.client().get(url).build().unwrap(),
-            no_options,
+            self.scoped(fill_options),
             Some(self.auth.clone()),
         )
     }
@@ -490,7 +691,7 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().post(url).build().unwrap(),
-            no_options,
+            self.scoped(no_options),
             Some(self.auth.clone()),
         )
     }
@@ -546,24 +747,173 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().post(url).build().unwrap(),
-            no_options,
+            self.scoped(no_options),
+            Some(self.auth.clone()),
+        )
+    }
+    /// Get a list of deposits from the profile of the API key, in descending order by created time.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// # use futures::TryStreamExt;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let mut deposits = client
+    ///     .list_deposits()
+    ///     .paginate::<serde_json::Value>()?;
+    ///
+    /// while let Some(json) = deposits.try_next().await? {
+    ///     println!("{}", serde_json::to_string_pretty(&json).unwrap());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn list_deposits<'a>(&self) -> QueryBuilder<PageOptions<'a>> {
+        let mut page_options = PageOptions::new();
+        page_options.params_mut().type_ = Some("deposit");
+
+        let url = self.url().join("/transfers").unwrap();
+        QueryBuilder::new(
+            self.client().clone(),
+            self.client().get(url).build().unwrap(),
+            self.scoped(page_options),
+            Some(self.auth.clone()),
+        )
+    }
+    /// Get a list of withdrawals from the profile of the API key, in descending order by created time.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// # use futures::TryStreamExt;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let mut withdrawals = client
+    ///     .list_withdrawals()
+    ///     .paginate::<serde_json::Value>()?;
+    ///
+    /// while let Some(json) = withdrawals.try_next().await? {
+    ///     println!("{}", serde_json::to_string_pretty(&json).unwrap());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn list_withdrawals<'a>(&self) -> QueryBuilder<PageOptions<'a>> {
+        let mut page_options = PageOptions::new();
+        page_options.params_mut().type_ = Some("withdraw");
+
+        let url = self.url().join("/transfers").unwrap();
+        QueryBuilder::new(
+            self.client().clone(),
+            self.client().get(url).build().unwrap(),
+            self.scoped(page_options),
+            Some(self.auth.clone()),
+        )
+    }
+    /// Get information on a single deposit or withdrawal, including its completion status.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let transfer = client
+    ///     .get_transfer("<transfer_id>")
+    ///     .json::<serde_json::Value>()
+    ///     .await?;
+    /// 
+    /// println!("{}", serde_json::to_string_pretty(&transfer).unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_transfer<'a>(&self, transfer_id: &str) -> QueryBuilder<NoOptions<'a>> {
+        let endpoint = format!("/transfers/{}", transfer_id);
+        let url = self.url().join(&endpoint).unwrap();
+        QueryBuilder::new(
+            self.client().clone(),
+            self.client().get(url).build().unwrap(),
+            self.scoped(NoOptions::new()),
+            Some(self.auth.clone()),
+        )
+    }
+    /// Get the network fee estimate when sending to the given crypto address.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let fee = client
+    ///     .get_withdrawal_fee_estimate("BTC", "<crypto_address>")
+    ///     .json::<serde_json::Value>()
+    ///     .await?;
+    /// 
+    /// println!("{}", serde_json::to_string_pretty(&fee).unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_withdrawal_fee_estimate<'a>(&self, currency: &'a str, crypto_address: &'a str) -> QueryBuilder<NoOptions<'a>> {
+        let mut no_options = NoOptions::new();
+        no_options.params_mut().currency = Some(currency);
+        no_options.params_mut().crypto_address = Some(crypto_address);
+
+        let url = self.url().join("/withdrawals/fee-estimate").unwrap();
+        QueryBuilder::new(
+            self.client().clone(),
+            self.client().get(url).build().unwrap(),
+            self.scoped(no_options),
+            Some(self.auth.clone()),
+        )
+    }
+    /// Generate a one-time crypto address for depositing crypto into a coinbase account.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let address = client
+    ///     .generate_crypto_deposit_address("<coinbase_account_id>")
+    ///     .json::<serde_json::Value>()
+    ///     .await?;
+    /// 
+    /// println!("{}", serde_json::to_string_pretty(&address).unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_crypto_deposit_address<'a>(&self, coinbase_account_id: &str) -> QueryBuilder<NoOptions<'a>> {
+        let endpoint = format!("/coinbase-accounts/{}/addresses", coinbase_account_id);
+        let url = self.url().join(&endpoint).unwrap();
+        QueryBuilder::new(
+            self.client().clone(),
+            self.client().post(url).build().unwrap(),
+            self.scoped(NoOptions::new()),
             Some(self.auth.clone()),
         )
     }
     /// Convert $10,000.00 to 10,000.00 USDC.
+    ///
+    /// Unsupported pairs (see [CONVERSIONS](constant.CONVERSIONS.html)) and non positive amounts are rejected locally.
     /// # Example
     ///
     /// ```no_run
     /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// use cbpro::models::Conversion;
     /// # #[tokio::main]
     /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
-    /// let response = client
+    /// let conversion = client
     ///     .convert("USD", "USDC", 100.00)
-    ///     .json::<serde_json::Value>()
+    ///     .json::<Conversion>()
     ///     .await?;
     /// 
-    /// println!("{}", serde_json::to_string_pretty(&response).unwrap());
+    /// println!("{:?}", conversion);
     /// # Ok(())
     /// # }
     /// ```
@@ -574,10 +924,48 @@ impl AuthenticatedClient {
         no_options.params_mut().amount = Some(amount);
 
         let url = self.url().join("/conversions").unwrap();
-        QueryBuilder::new(
+        let builder = QueryBuilder::new(
             self.client().clone(),
             self.client().post(url).build().unwrap(),
-            no_options,
+            self.scoped(no_options),
+            Some(self.auth.clone()),
+        );
+
+        if !CONVERSIONS.contains(&(from, to)) {
+            let message = format!("Unsupported conversion from {} to {}", from, to);
+            builder.fail(Error::new(Kind::Conversion, Some(message)))
+        } else if !amount.is_finite() || amount <= 0.0 {
+            let message = format!("Invalid conversion amount {}", amount);
+            builder.fail(Error::new(Kind::Conversion, Some(message)))
+        } else {
+            builder
+        }
+    }
+    /// Get a previously created conversion by id.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// use cbpro::models::Conversion;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let conversion = client
+    ///     .get_conversion("<conversion_id>")
+    ///     .json::<Conversion>()
+    ///     .await?;
+    /// 
+    /// println!("{:?}", conversion);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_conversion<'a>(&self, conversion_id: &str) -> QueryBuilder<NoOptions<'a>> {
+        let endpoint = format!("/conversions/{}", conversion_id);
+        let url = self.url().join(&endpoint).unwrap();
+        QueryBuilder::new(
+            self.client().clone(),
+            self.client().get(url).build().unwrap(),
+            self.scoped(NoOptions::new()),
             Some(self.auth.clone()),
         )
     }
@@ -706,10 +1094,39 @@ impl AuthenticatedClient {
         QueryBuilder::new(
             self.client().clone(),
             self.client().post(url).build().unwrap(),
-            report_options,
+            self.scoped(report_options),
             Some(self.auth.clone()),
         )
     }
+    /// Creates a report, polls its status with backoff until it is ready and downloads the file.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// use cbpro::client::RPT;
+    /// use chrono::{ TimeZone, Utc };
+    /// 
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let start_date = Utc.ymd(2018, 8, 10).and_hms(0, 0, 0);
+    /// let end_date = Utc.ymd(2018, 8, 28).and_hms(0, 0, 0);
+    ///
+    /// let rows = client
+    ///     .generate_report(start_date, end_date, RPT::Account { account_id: "<account_id>" })
+    ///     .account()
+    ///     .await?;
+    /// 
+    /// println!("{:?}", rows);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_report<'a, Tz: TimeZone>(&self, start_date: DateTime<Tz>, end_date: DateTime<Tz>, rpt: RPT<'a>) -> ReportRequest<'a> 
+        where
+            Tz::Offset: core::fmt::Display,
+    {
+        ReportRequest::new(self.clone(), self.create_report(start_date, end_date, rpt))
+    }
     /// Once a report request has been accepted for processing, the status is available by polling the report resource endpoint.
     ///
     /// The final report will be uploaded and available at file_url once the status indicates ready
@@ -730,7 +1147,7 @@ impl AuthenticatedClient {
     /// # }
     /// ```
     pub fn get_report_status<'a>(&self, report_id: &'a str) -> QueryBuilder<NoOptions<'a>> {
-        let endpoint = format!("/reports/:{}", report_id);
+        let endpoint = format!("/reports/{}", report_id);
         let url = self.url().join(&endpoint).unwrap();
         QueryBuilder::new(
             self.client().clone(),
@@ -856,6 +1273,7 @@ impl AuthenticatedClient {
 }
 
 /// Public client
+#[derive(Clone)]
 pub struct PublicClient {
     client: Client,
     url: Url,
@@ -989,6 +1407,37 @@ impl PublicClient {
             None,
         )
     }
+    /// Trades of a product for [backfill](../builder/struct.QueryBuilder.html#method.backfill),
+    /// walking backward from the latest trade or from `after`.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{PublicClient, SANDBOX_URL};
+    /// use cbpro::Until;
+    /// use futures::TryStreamExt;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = PublicClient::new(SANDBOX_URL);
+    /// let mut trades = client
+    ///     .backfill_trades("BTC-USD")
+    ///     .backfill(Until::TradeID(1000));
+    ///
+    /// while let Some(trade) = trades.try_next().await? {
+    ///     println!("{:?}", trade);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn backfill_trades<'a>(&self, product_id: &str) -> QueryBuilder<TradeOptions<'a>> {
+        let endpoint = format!("/products/{}/trades", product_id);
+        let url = self.url.join(&endpoint).unwrap();
+        QueryBuilder::new(
+            self.client.clone(),
+            self.client.get(url).build().unwrap(),
+            TradeOptions::new(),
+            None,
+        )
+    }
     /// Historic rates for a product. 
     /// Rates are returned in grouped buckets based on requested granularity.
     /// # Example
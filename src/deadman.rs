@@ -0,0 +1,286 @@
+use core::time::Duration;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::time::Instant;
+use futures::StreamExt;
+use crate::client::AuthenticatedClient;
+use crate::websocket::{Channels, WebSocketFeed};
+
+/// Reason the switch canceled the orders
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    /// `keepalive` was not called within the timeout
+    Keepalive,
+    /// The user websocket was down for too long
+    Disconnect,
+    /// `shutdown` was called
+    Shutdown,
+}
+
+/// Result of a triggered switch
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triggered {
+    pub trigger: Trigger,
+    /// Ids of the canceled orders
+    pub canceled: Vec<String>,
+}
+
+struct State {
+    keepalive: Mutex<Instant>,
+    shutdown: AtomicBool,
+}
+
+/// Handle used by the application to feed the [DeadMansSwitch](struct.DeadMansSwitch.html)
+#[derive(Clone)]
+pub struct SwitchHandle {
+    state: Arc<State>,
+}
+
+impl SwitchHandle {
+    /// Signals the application is alive.
+    pub fn keepalive(&self) {
+        *self.state.keepalive.lock().unwrap() = Instant::now();
+    }
+
+    /// Cancels the orders and stops the switch.
+    pub fn shutdown(&self) {
+        self.state.shutdown.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Watch {
+    url: String,
+    product_ids: Vec<String>,
+    max_down: Duration,
+}
+
+/// Cancels all open orders when the application stops calling `keepalive`, the user websocket
+/// is down for too long or on shutdown.
+///
+/// The switch is a future that has to be spawned or polled next to the application, it resolves once triggered.
+/// Dropping the handles does not stop the switch, without keepalives it triggers after the timeout.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+/// use cbpro::deadman::DeadMansSwitch;
+/// use cbpro::websocket::SANDBOX_FEED_URL;
+/// use core::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+/// let switch = DeadMansSwitch::new(client.clone(), Duration::from_secs(10))
+///     .product_id("BTC-USD")
+///     .watch_feed(SANDBOX_FEED_URL, &["BTC-USD"], Duration::from_secs(5));
+/// let handle = switch.handle();
+///
+/// let app = async {
+///     for _ in 0..10 {
+///         handle.keepalive();
+///         tokio::time::delay_for(Duration::from_secs(1)).await;
+///     }
+///     handle.shutdown();
+/// };
+///
+/// let (_, triggered) = futures::join!(app, switch.run());
+/// println!("{:?}", triggered?);
+/// # Ok(())
+/// # }
+/// ```
+pub struct DeadMansSwitch {
+    client: AuthenticatedClient,
+    timeout: Duration,
+    product_id: Option<String>,
+    watch: Option<Watch>,
+    retries: u32,
+    state: Arc<State>,
+}
+
+impl DeadMansSwitch {
+    /// Creates a switch that triggers when `keepalive` is not called within the timeout.
+    pub fn new(client: AuthenticatedClient, timeout: Duration) -> Self {
+        Self {
+            client,
+            timeout,
+            product_id: None,
+            watch: None,
+            retries: 5,
+            state: Arc::new(State {
+                keepalive: Mutex::new(Instant::now()),
+                shutdown: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Only cancels the orders of a single product.
+    pub fn product_id<P: Into<String>>(mut self, value: P) -> Self {
+        self.product_id = Some(value.into());
+        self
+    }
+
+    /// Also triggers when the user channel of the given products receives no message for `max_down`,
+    /// including heartbeats.
+    pub fn watch_feed<U: Into<String>>(mut self, url: U, product_ids: &[&str], max_down: Duration) -> Self {
+        self.watch = Some(Watch {
+            url: url.into(),
+            product_ids: product_ids.iter().map(|id| id.to_string()).collect(),
+            max_down,
+        });
+        self
+    }
+
+    /// Sets how often a failed `cancel_all` is retried, defaults to 5.
+    pub fn retries(mut self, value: u32) -> Self {
+        self.retries = value;
+        self
+    }
+
+    /// Returns a handle to feed and stop the switch.
+    pub fn handle(&self) -> SwitchHandle {
+        SwitchHandle { state: self.state.clone() }
+    }
+
+    /// Watches the application and the feed, cancels all orders once triggered.
+    pub async fn run(self) -> crate::error::Result<Triggered> {
+        let mut tick = (self.timeout / 4).min(Duration::from_secs(1));
+        if let Some(ref watch) = self.watch {
+            tick = tick.min(watch.max_down / 4);
+        }
+
+        let mut feed: Option<WebSocketFeed> = None;
+        let mut last_message = Instant::now();
+        loop {
+            if let Some(trigger) = self.check(last_message) {
+                return self.trigger(trigger).await;
+            }
+
+            let watch = match self.watch {
+                Some(ref watch) => watch,
+                None => {
+                    tokio::time::delay_for(tick).await;
+                    continue;
+                }
+            };
+
+            match feed.take() {
+                Some(mut connected) => match tokio::time::timeout(tick, connected.next()).await {
+                    Ok(Some(Ok(_))) => {
+                        last_message = Instant::now();
+                        feed = Some(connected);
+                    }
+                    Ok(Some(Err(_))) | Ok(None) => (),
+                    Err(_) => feed = Some(connected),
+                },
+                None => {
+                    let remaining = watch.max_down.checked_sub(last_message.elapsed()).unwrap_or_default();
+                    match tokio::time::timeout(remaining.max(tick), self.connect(watch)).await {
+                        Ok(Ok(connected)) => {
+                            last_message = Instant::now();
+                            feed = Some(connected);
+                        }
+                        _ => tokio::time::delay_for(tick).await,
+                    }
+                }
+            }
+        }
+    }
+
+    fn check(&self, last_message: Instant) -> Option<Trigger> {
+        if self.state.shutdown.load(Ordering::SeqCst) {
+            return Some(Trigger::Shutdown);
+        }
+        if self.state.keepalive.lock().unwrap().elapsed() > self.timeout {
+            return Some(Trigger::Keepalive);
+        }
+        match self.watch {
+            Some(ref watch) if last_message.elapsed() > watch.max_down => Some(Trigger::Disconnect),
+            _ => None,
+        }
+    }
+
+    async fn connect(&self, watch: &Watch) -> crate::error::Result<WebSocketFeed> {
+        let auth = self.client.auth();
+        let mut feed = WebSocketFeed::connect_auth(auth.key.clone(), auth.pass.clone(), auth.secret.clone(), watch.url.clone()).await?;
+        let product_ids: Vec<&str> = watch.product_ids.iter().map(String::as_str).collect();
+        feed.subscribe(&product_ids, &[Channels::USER, Channels::HEARTBEAT]).await?;
+        Ok(feed)
+    }
+
+    async fn trigger(&self, trigger: Trigger) -> crate::error::Result<Triggered> {
+        let mut delay = Duration::from_secs(1);
+        let mut attempt = 0;
+        loop {
+            let mut cancel = self.client.cancel_all();
+            if let Some(ref product_id) = self.product_id {
+                cancel = cancel.product_id(product_id);
+            }
+            match cancel.json::<Vec<String>>().await {
+                Ok(canceled) => return Ok(Triggered { trigger, canceled }),
+                Err(error) if attempt >= self.retries => return Err(error),
+                Err(_) => {
+                    attempt += 1;
+                    tokio::time::delay_for(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SANDBOX_URL;
+    use crate::websocket::SANDBOX_FEED_URL;
+
+    fn switch(timeout: Duration) -> DeadMansSwitch {
+        let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+        DeadMansSwitch::new(client, timeout)
+    }
+
+    #[tokio::test]
+    async fn keepalive_trigger() {
+        let switch = switch(Duration::from_millis(100));
+        let handle = switch.handle();
+        assert_eq!(switch.check(Instant::now()), None);
+
+        tokio::time::delay_for(Duration::from_millis(60)).await;
+        handle.keepalive();
+        tokio::time::delay_for(Duration::from_millis(60)).await;
+        assert_eq!(switch.check(Instant::now()), None);
+
+        tokio::time::delay_for(Duration::from_millis(60)).await;
+        assert_eq!(switch.check(Instant::now()), Some(Trigger::Keepalive));
+    }
+
+    #[test]
+    fn shutdown_trigger() {
+        let switch = switch(Duration::from_secs(60));
+        let handle = switch.handle();
+        handle.clone().shutdown();
+        assert_eq!(switch.check(Instant::now()), Some(Trigger::Shutdown));
+    }
+
+    #[test]
+    fn dropped_or_missing_handles_do_not_trigger() {
+        let switch = switch(Duration::from_secs(60));
+        assert_eq!(switch.check(Instant::now()), None);
+        drop(switch.handle());
+        assert_eq!(switch.check(Instant::now()), None);
+    }
+
+    #[tokio::test]
+    async fn disconnect_trigger() {
+        let mut switch = switch(Duration::from_secs(60));
+        switch.watch = Some(Watch { url: SANDBOX_FEED_URL.to_string(), product_ids: Vec::new(), max_down: Duration::from_millis(20) });
+        let last_message = Instant::now();
+        assert_eq!(switch.check(last_message), None);
+
+        tokio::time::delay_for(Duration::from_millis(30)).await;
+        assert_eq!(switch.check(last_message), Some(Trigger::Disconnect));
+        assert_eq!(switch.check(Instant::now()), None);
+    }
+}
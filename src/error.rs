@@ -15,6 +15,15 @@ pub(super) enum Kind {
     Base64,
     Serde,
     Hmac,
+    Cursor,
+    Conversion,
+    Report,
+    Csv,
+    Io,
+    Execution,
+    Risk,
+    Liveness,
+    Order,
 }
 
 /// General error type.
@@ -33,6 +42,18 @@ impl Error {
             source: source.map(Into::into)
         }
     }
+
+    /// Returns the http status code of errors returned by Coinbase.
+    pub fn status(&self) -> Option<u16> {
+        let source = self.source.as_ref()?;
+        source.downcast_ref::<CBError>().map(|error| error.code)
+    }
+
+    /// Returns the violation of orders rejected by a [RiskGate](../risk/struct.RiskGate.html).
+    pub fn risk(&self) -> Option<&crate::risk::RiskViolation> {
+        let source = self.source.as_ref()?;
+        source.downcast_ref::<crate::risk::RiskViolation>()
+    }
 }
 
 impl fmt::Debug for Error {
@@ -71,6 +92,12 @@ impl From<CBError> for Error {
     }
 }
 
+impl From<FeedError> for Error {
+    fn from(error: FeedError) -> Self {
+        Error::new(Kind::Coinbase, Some(error))
+    }
+}
+
 impl From<WsCloseError> for Error {
     fn from(error: WsCloseError) -> Self {
         Error::new(Kind::Coinbase, Some(error))
@@ -125,6 +152,18 @@ impl From<hmac::crypto_mac::InvalidKeyLength> for Error {
     }
 }
 
+impl From<csv::Error> for Error {
+    fn from(error: csv::Error) -> Self {
+        Error::new(Kind::Csv, Some(error))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::new(Kind::Io, Some(error))
+    }
+}
+
 /// Coinbase specific error types.
 #[derive(Debug)]
 pub struct CBError {
@@ -172,4 +211,31 @@ impl error::Error for WsCloseError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         None
     }
-}
\ No newline at end of file
+}
+/// Error message sent by the websocket feed, e.g. for a rejected subscription.
+#[derive(Debug)]
+pub struct FeedError {
+    message: String,
+    reason: Option<String>,
+}
+
+impl FeedError {
+    pub(super) fn new(message: String, reason: Option<String>) -> Self {
+        FeedError { message, reason }
+    }
+}
+
+impl fmt::Display for FeedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            Some(ref reason) => write!(f, "{}: {}", self.message, reason),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl error::Error for FeedError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
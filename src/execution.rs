@@ -0,0 +1,651 @@
+use core::time::Duration;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Instant;
+use crate::client::{AuthenticatedClient, ORD, QTY};
+use crate::error::{Error, Kind};
+use crate::models::{FeedMessage, Order, Product, Ticker};
+use crate::websocket::{Channels, WebSocketFeed};
+
+const EPSILON: f64 = 1e-9;
+
+/// Execution algorithm of an [Execution](struct.Execution.html)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algo {
+    /// Splits the size into market orders spread evenly over `duration`
+    Twap { duration: Duration, slices: u32 },
+    /// Shows a limit order of `clip` at `price`, the next clip is placed once it filled
+    Iceberg { price: f64, clip: f64 },
+    /// Rests a post only limit order at the best bid of a buy or the best ask of a sell,
+    /// `offset` moves the price away from the touch
+    Peg { offset: f64 },
+}
+
+/// State of an [Execution](struct.Execution.html)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExecutionState {
+    Working,
+    /// The size is filled, or all slices of a TWAP were sent
+    Completed,
+    /// Stopped by [ExecutionHandle::cancel](struct.ExecutionHandle.html#method.cancel)
+    Canceled,
+}
+
+/// Progress reported by [Execution::next](struct.Execution.html#method.next)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub state: ExecutionState,
+    pub size: f64,
+    pub filled_size: f64,
+    /// Quote amount of all fills excluding fees
+    pub executed_value: f64,
+    pub orders_placed: u32,
+    /// Id of the order currently working
+    pub working_order: Option<String>,
+}
+
+impl Progress {
+    /// Returns the size left to fill.
+    pub fn remaining(&self) -> f64 {
+        (self.size - self.filled_size).max(0.0)
+    }
+
+    /// Returns the average price of all fills.
+    pub fn average_price(&self) -> Option<f64> {
+        if self.filled_size > 0.0 {
+            Some(self.executed_value / self.filled_size)
+        } else {
+            None
+        }
+    }
+}
+
+/// Handle to cancel a running [Execution](struct.Execution.html)
+#[derive(Clone)]
+pub struct ExecutionHandle {
+    canceled: Arc<AtomicBool>,
+}
+
+impl ExecutionHandle {
+    /// Cancels the working order and stops the execution on the next call to `next`.
+    pub fn cancel(&self) {
+        self.canceled.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Working {
+    id: String,
+    price: Option<f64>,
+    market: bool,
+    filled_size: f64,
+    executed_value: f64,
+    size: f64,
+    placed_at: Instant,
+}
+
+/// Works an order with an execution algorithm, driven by the `ticker` and `matches` channels of the public feed.
+///
+/// Prices and sizes are rounded to the increments of the product, fills are counted from `match` messages and
+/// confirmed over REST whenever an order is retired. While the feed is down the working order is polled with `get_order`.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+/// use cbpro::execution::{Algo, Execution};
+/// use cbpro::websocket::SANDBOX_FEED_URL;
+/// use core::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+/// let algo = Algo::Twap { duration: Duration::from_secs(600), slices: 10 };
+/// let mut execution = Execution::new(client, SANDBOX_FEED_URL, "BTC-USD", "buy", 1.0, algo);
+/// let handle = execution.handle();
+///
+/// while let Some(progress) = execution.next().await? {
+///     println!("{:?} filled {} @ {:?}", progress.state, progress.filled_size, progress.average_price());
+///     if progress.filled_size > 0.5 {
+///         handle.cancel();
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Execution {
+    client: AuthenticatedClient,
+    url: String,
+    product_id: String,
+    side: String,
+    algo: Algo,
+    product: Option<Product>,
+    feed: Option<WebSocketFeed>,
+    connected_at: Option<Instant>,
+    best_bid: Option<f64>,
+    best_ask: Option<f64>,
+    working: Option<Working>,
+    progress: Progress,
+    // exact fills of retired orders
+    done_size: f64,
+    done_value: f64,
+    started: Instant,
+    slices_sent: u32,
+    reprice_interval: Duration,
+    reconnect_interval: Duration,
+    tick: Duration,
+    canceled: Arc<AtomicBool>,
+    finished: bool,
+}
+
+impl Execution {
+    /// Creates an execution of `size` base currency, nothing is placed before the first call to `next`.
+    pub fn new<U, P, S>(client: AuthenticatedClient, feed_url: U, product_id: P, side: S, size: f64, algo: Algo) -> Self
+    where
+        U: Into<String>,
+        P: Into<String>,
+        S: Into<String>,
+    {
+        Self {
+            client,
+            url: feed_url.into(),
+            product_id: product_id.into(),
+            side: side.into(),
+            algo,
+            product: None,
+            feed: None,
+            connected_at: None,
+            best_bid: None,
+            best_ask: None,
+            working: None,
+            progress: Progress {
+                state: ExecutionState::Working,
+                size,
+                filled_size: 0.0,
+                executed_value: 0.0,
+                orders_placed: 0,
+                working_order: None,
+            },
+            done_size: 0.0,
+            done_value: 0.0,
+            started: Instant::now(),
+            slices_sent: 0,
+            reprice_interval: Duration::from_secs(1),
+            reconnect_interval: Duration::from_secs(10),
+            tick: Duration::from_secs(1),
+            canceled: Arc::new(AtomicBool::new(false)),
+            finished: false,
+        }
+    }
+
+    /// Sets the minimum time between two reprices of a peg, defaults to 1 second.
+    pub fn reprice_interval(mut self, value: Duration) -> Self {
+        self.reprice_interval = value;
+        self
+    }
+
+    /// Sets the delay between connection attempts, defaults to 10 seconds.
+    pub fn reconnect_interval(mut self, value: Duration) -> Self {
+        self.reconnect_interval = value;
+        self
+    }
+
+    /// Returns a handle to cancel the execution.
+    pub fn handle(&self) -> ExecutionHandle {
+        ExecutionHandle { canceled: self.canceled.clone() }
+    }
+
+    /// Returns the current progress.
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// Drives the execution until its progress changes.
+    /// Returns the final progress once completed or canceled and `None` afterwards.
+    pub async fn next(&mut self) -> crate::error::Result<Option<Progress>> {
+        if self.finished {
+            return Ok(None);
+        }
+        if self.product.is_none() {
+            self.start().await?;
+        }
+
+        let before = self.progress.clone();
+        loop {
+            if self.canceled.load(Ordering::SeqCst) {
+                if self.working.is_some() {
+                    self.retire(true).await?;
+                }
+                self.progress.state = ExecutionState::Canceled;
+            } else {
+                self.step().await?;
+            }
+            self.update_progress();
+
+            if self.progress.state != ExecutionState::Working {
+                self.finished = true;
+                if let Some(feed) = self.feed.take() {
+                    feed.close().await.ok();
+                }
+                return Ok(Some(self.progress.clone()));
+            }
+            if self.progress != before {
+                return Ok(Some(self.progress.clone()));
+            }
+            self.read().await?;
+        }
+    }
+
+    async fn start(&mut self) -> crate::error::Result<()> {
+        let products: Vec<Product> = self.client.public().get_products().json().await?;
+        let product = products
+            .into_iter()
+            .find(|product| product.id == self.product_id)
+            .ok_or_else(|| Error::new(Kind::Execution, Some(format!("unknown product {}", self.product_id))))?;
+        self.product = Some(product);
+
+        if let Algo::Peg { .. } = self.algo {
+            let ticker: Ticker = self.client.public().get_product_ticker(&self.product_id).json().await?;
+            self.best_bid = Some(ticker.bid);
+            self.best_ask = Some(ticker.ask);
+        }
+        self.started = Instant::now();
+        Ok(())
+    }
+
+    async fn step(&mut self) -> crate::error::Result<()> {
+        if let Some(ref working) = self.working {
+            let filled = working.filled_size >= working.size - EPSILON;
+            // market orders are done within moments, confirm them even if a match was missed
+            let settled = working.market && working.placed_at.elapsed() >= self.tick;
+            if filled || settled {
+                self.retire(false).await?;
+            }
+        }
+
+        let remaining = self.remaining();
+        match self.algo {
+            Algo::Twap { duration, slices } => {
+                let slices = slices.max(1);
+                let due = self.started + duration * self.slices_sent / slices;
+                if self.slices_sent < slices && Instant::now() >= due {
+                    if self.working.is_some() {
+                        self.retire(true).await?;
+                    }
+                    let remaining = self.remaining();
+                    let size = if self.slices_sent + 1 == slices {
+                        remaining
+                    } else {
+                        self.floor_size(remaining / (slices - self.slices_sent) as f64)
+                    };
+                    self.slices_sent += 1;
+                    // slices below the minimum size are carried over to the next one
+                    if size >= self.min_size() {
+                        self.place(None, size).await?;
+                    }
+                } else if self.slices_sent >= slices && self.working.is_none() {
+                    self.progress.state = ExecutionState::Completed;
+                }
+            }
+            Algo::Iceberg { price, clip } => {
+                if self.working.is_none() {
+                    let size = self.floor_size(clip.min(remaining));
+                    if remaining < self.min_size() {
+                        self.progress.state = ExecutionState::Completed;
+                    } else {
+                        let price = self.round_price(price);
+                        self.place(Some(price), size.max(self.min_size()).min(remaining)).await?;
+                    }
+                }
+            }
+            Algo::Peg { offset } => {
+                let target = match (self.side.as_str(), self.best_bid, self.best_ask) {
+                    ("buy", Some(bid), _) => Some(self.round_price(bid - offset)),
+                    ("sell", _, Some(ask)) => Some(self.round_price(ask + offset)),
+                    _ => None,
+                };
+                let stale = match (&self.working, target) {
+                    (Some(working), Some(target)) => {
+                        working.price != Some(target) && working.placed_at.elapsed() >= self.reprice_interval
+                    }
+                    _ => false,
+                };
+                if stale {
+                    self.retire(true).await?;
+                }
+                let remaining = self.remaining();
+                if self.working.is_none() {
+                    if remaining < self.min_size() {
+                        self.progress.state = ExecutionState::Completed;
+                    } else if let Some(target) = target {
+                        self.place(Some(target), remaining).await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn place(&mut self, price: Option<f64>, size: f64) -> crate::error::Result<()> {
+        let order: Order = match price {
+            Some(price) => {
+                let mut builder = self.client.place_limit_order(&self.product_id, &self.side, price, size);
+                if let Algo::Peg { .. } = self.algo {
+                    builder = builder.post_only(true);
+                }
+                builder.json().await?
+            }
+            None => self.client.place_market_order(&self.product_id, &self.side, QTY::Size(size)).json().await?,
+        };
+        self.progress.orders_placed += 1;
+        // post only orders crossing the book are rejected, the peg retries on the next tick
+        if order.status != "rejected" {
+            self.working = Some(Working {
+                id: order.id,
+                price,
+                market: price.is_none(),
+                filled_size: 0.0,
+                executed_value: 0.0,
+                size,
+                placed_at: Instant::now(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Confirms the fills of the working order over REST and drops it once done, cancels it first if asked.
+    async fn retire(&mut self, cancel: bool) -> crate::error::Result<()> {
+        let id = match self.working {
+            Some(ref working) => working.id.clone(),
+            None => return Ok(()),
+        };
+        if cancel {
+            match self.client.cancel_order(ORD::OrderID(&id)).text().await {
+                Ok(_) => (),
+                Err(ref error) if matches!(error.status(), Some(400) | Some(404)) => (),
+                Err(error) => return Err(error),
+            }
+        }
+
+        match self.client.get_order(ORD::OrderID(&id)).json::<Order>().await {
+            Ok(order) if cancel || order.status == "done" => {
+                self.done_size += order.filled_size;
+                self.done_value += order.executed_value;
+                self.working = None;
+            }
+            Ok(order) => {
+                let working = self.working.as_mut().unwrap();
+                working.filled_size = working.filled_size.max(order.filled_size);
+                working.executed_value = working.executed_value.max(order.executed_value);
+            }
+            // orders canceled without fills are purged
+            Err(ref error) if error.status() == Some(404) && cancel => self.working = None,
+            Err(ref error) if error.status() == Some(404) => (),
+            Err(error) => return Err(error),
+        }
+        Ok(())
+    }
+
+    async fn read(&mut self) -> crate::error::Result<()> {
+        if let Some(mut feed) = self.feed.take() {
+            match tokio::time::timeout(self.tick, feed.json::<serde_json::Value>()).await {
+                Ok(Ok(Some(value))) => {
+                    self.feed = Some(feed);
+                    self.apply(&value)?;
+                }
+                Ok(_) => (),
+                Err(_) => self.feed = Some(feed),
+            }
+            return Ok(());
+        }
+
+        let due = match self.connected_at {
+            Some(at) => at.elapsed() >= self.reconnect_interval,
+            None => true,
+        };
+        if due {
+            self.connected_at = Some(Instant::now());
+            self.feed = self.connect().await.ok();
+        }
+        if self.feed.is_none() {
+            self.retire(false).await?;
+            tokio::time::delay_for(self.tick).await;
+        }
+        Ok(())
+    }
+
+    async fn connect(&self) -> crate::error::Result<WebSocketFeed> {
+        let mut feed = WebSocketFeed::connect(self.url.clone()).await?;
+        feed.subscribe(&[&self.product_id], &[Channels::TICKER, Channels::MATCHES]).await?;
+        Ok(feed)
+    }
+
+    fn apply(&mut self, value: &serde_json::Value) -> crate::error::Result<()> {
+        let message: FeedMessage = serde_json::from_value(value.clone())?;
+        if message.product_id.as_deref() != Some(self.product_id.as_str()) {
+            return Ok(());
+        }
+        match message.type_.as_str() {
+            "ticker" => {
+                self.best_bid = message.best_bid.or(self.best_bid);
+                self.best_ask = message.best_ask.or(self.best_ask);
+            }
+            "match" => {
+                if let (Some(working), Some(price), Some(size)) = (self.working.as_mut(), message.price, message.size) {
+                    let id = Some(&working.id);
+                    if message.maker_order_id.as_ref() == id || message.taker_order_id.as_ref() == id {
+                        working.filled_size += size;
+                        working.executed_value += price * size;
+                    }
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn update_progress(&mut self) {
+        let (size, value) = match self.working {
+            Some(ref working) => (working.filled_size, working.executed_value),
+            None => (0.0, 0.0),
+        };
+        self.progress.filled_size = self.done_size + size;
+        self.progress.executed_value = self.done_value + value;
+        self.progress.working_order = self.working.as_ref().map(|working| working.id.clone());
+    }
+
+    fn remaining(&self) -> f64 {
+        let working = self.working.as_ref().map_or(0.0, |working| working.size);
+        self.floor_size(self.progress.size - self.done_size - working)
+    }
+
+    fn min_size(&self) -> f64 {
+        let product = self.product.as_ref().unwrap();
+        product.base_min_size.max(product.base_increment)
+    }
+
+    fn floor_size(&self, size: f64) -> f64 {
+        let increment = self.product.as_ref().unwrap().base_increment;
+        snap((size / increment + EPSILON).floor() * increment, increment).max(0.0)
+    }
+
+    /// Rounds a price to the quote increment, towards the passive side.
+    fn round_price(&self, price: f64) -> f64 {
+        let increment = self.product.as_ref().unwrap().quote_increment;
+        let steps = price / increment;
+        let steps = if self.side == "buy" { (steps + EPSILON).floor() } else { (steps - EPSILON).ceil() };
+        snap(steps * increment, increment)
+    }
+}
+
+/// Drops the float noise below the decimals of an increment.
+pub(crate) fn snap(value: f64, increment: f64) -> f64 {
+    let factor = 10f64.powi((-increment.log10()).ceil().max(0.0) as i32);
+    (value * factor).round() / factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use serde_json::json;
+    use crate::mock::{self, Received, Reply, Requests};
+
+    fn product() -> Product {
+        serde_json::from_value(json!({
+            "id": "BTC-USD",
+            "base_currency": "BTC",
+            "quote_currency": "USD",
+            "base_increment": "0.001",
+            "quote_increment": "0.01",
+            "base_min_size": "0.001",
+        }))
+        .unwrap()
+    }
+
+    fn order(id: &str, size: f64, done: bool) -> serde_json::Value {
+        json!({
+            "id": id,
+            "product_id": "BTC-USD",
+            "side": "buy",
+            "type": "limit",
+            "size": size.to_string(),
+            "status": if done { "done" } else { "pending" },
+            "done_reason": if done { Some("filled") } else { None },
+            "filled_size": if done { size } else { 0.0 }.to_string(),
+            "executed_value": if done { size * 100.0 } else { 0.0 }.to_string(),
+            "created_at": "2020-01-01T00:00:00Z",
+        })
+    }
+
+    /// Places orders and reports them filled once asked for.
+    fn exchange(request: &Received, orders: &mut HashMap<String, f64>) -> Reply {
+        let path = request.url.path();
+        match request.method.as_str() {
+            "POST" if path == "/orders" => {
+                let id = format!("order-{}", orders.len() + 1);
+                let size = request.json()["size"].as_f64().unwrap();
+                orders.insert(id.clone(), size);
+                Reply::ok(order(&id, size, false))
+            }
+            "GET" => {
+                let id = path.trim_start_matches("/orders/");
+                Reply::ok(order(id, orders[id], true))
+            }
+            "DELETE" => Reply::ok(json!([path.trim_start_matches("/orders/")])),
+            _ => Reply::status(404, json!({"message": "NotFound"})),
+        }
+    }
+
+    fn execution(algo: Algo, size: f64) -> (Execution, Requests) {
+        let mut orders = HashMap::new();
+        let (url, requests) = mock::serve(move |request| exchange(request, &mut orders));
+        let client = AuthenticatedClient::new("key", "pass", "c2VjcmV0", url.as_str());
+        let mut execution = Execution::new(client, "ws://127.0.0.1:9", "BTC-USD", "buy", size, algo);
+        execution.product = Some(product());
+        execution.tick = Duration::from_secs(0);
+        (execution, requests)
+    }
+
+    fn placed(requests: &Requests) -> Vec<(Option<f64>, f64)> {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.method == "POST")
+            .map(|request| {
+                let body = request.json();
+                (body["price"].as_f64(), body["size"].as_f64().unwrap())
+            })
+            .collect()
+    }
+
+    async fn step(execution: &mut Execution) {
+        execution.step().await.unwrap();
+        execution.update_progress();
+    }
+
+    fn fill(execution: &mut Execution) {
+        let working = execution.working.as_ref().unwrap();
+        let message = json!({
+            "type": "match",
+            "product_id": "BTC-USD",
+            "maker_order_id": working.id,
+            "taker_order_id": "other",
+            "price": "100.00",
+            "size": working.size.to_string(),
+        });
+        execution.apply(&message).unwrap();
+    }
+
+    #[test]
+    fn sizes_and_prices_are_rounded_to_increments() {
+        let (mut execution, _) = execution(Algo::Peg { offset: 0.0 }, 1.0);
+
+        assert_eq!(execution.floor_size(0.3335), 0.333);
+        assert_eq!(execution.floor_size(0.1 + 0.2), 0.3);
+        assert_eq!(execution.floor_size(-0.0001), 0.0);
+
+        assert_eq!(execution.round_price(100.019), 100.01);
+        assert_eq!(execution.round_price(100.1), 100.1);
+        execution.side = "sell".to_string();
+        assert_eq!(execution.round_price(100.011), 100.02);
+        assert_eq!(execution.round_price(100.1), 100.1);
+
+        assert_eq!(snap(0.1 + 0.2, 0.01), 0.3);
+        assert_eq!(snap(1234.5, 1.0), 1235.0);
+    }
+
+    #[tokio::test]
+    async fn twap_splits_size_into_slices() {
+        let algo = Algo::Twap { duration: Duration::from_secs(0), slices: 3 };
+        let (mut execution, requests) = execution(algo, 1.0);
+
+        for _ in 0..4 {
+            step(&mut execution).await;
+        }
+
+        assert_eq!(placed(&requests), vec![(None, 0.333), (None, 0.333), (None, 0.334)]);
+        let progress = execution.progress();
+        assert_eq!(progress.state, ExecutionState::Completed);
+        assert_eq!(progress.orders_placed, 3);
+        assert!((progress.filled_size - 1.0).abs() < EPSILON);
+        assert_eq!(progress.average_price(), Some(100.0));
+    }
+
+    #[tokio::test]
+    async fn twap_carries_slices_below_min_size() {
+        let algo = Algo::Twap { duration: Duration::from_secs(0), slices: 10 };
+        let (mut execution, requests) = execution(algo, 0.005);
+
+        for _ in 0..11 {
+            step(&mut execution).await;
+        }
+
+        assert_eq!(placed(&requests), vec![(None, 0.001); 5]);
+        assert_eq!(execution.progress().state, ExecutionState::Completed);
+        assert!((execution.progress().filled_size - 0.005).abs() < EPSILON);
+    }
+
+    #[tokio::test]
+    async fn iceberg_places_next_clip_once_filled() {
+        let algo = Algo::Iceberg { price: 100.005, clip: 0.4 };
+        let (mut execution, requests) = execution(algo, 1.0);
+
+        step(&mut execution).await;
+        // nothing new while the clip rests unfilled
+        step(&mut execution).await;
+        assert_eq!(placed(&requests).len(), 1);
+
+        for _ in 0..3 {
+            fill(&mut execution);
+            step(&mut execution).await;
+        }
+
+        let price = Some(100.0);
+        assert_eq!(placed(&requests), vec![(price, 0.4), (price, 0.4), (price, 0.2)]);
+        assert_eq!(execution.progress().state, ExecutionState::Completed);
+        assert!((execution.progress().filled_size - 1.0).abs() < EPSILON);
+        assert!(execution.progress().remaining() < EPSILON);
+    }
+}
@@ -57,16 +57,49 @@
 //!     Ok(())
 //! }
 //! ```
+/// Batch order placement and cancellation
+pub mod batch;
+/// One-cancels-other take profit and stop loss
+pub mod bracket;
 /// Builder and types representing optional methods
 pub mod builder;
+/// Candle aggregation from trades
+pub mod candles;
 /// Public and private clients
 pub mod client;
+/// Cancel on disconnect
+pub mod deadman;
 /// Errors of this crate
 pub mod error;
+/// TWAP, iceberg and peg execution
+pub mod execution;
+#[cfg(test)]
+mod mock;
+/// Typed responses
+pub mod models;
 mod paging;
+/// Positions and PnL from fills
+pub mod pnl;
+/// Balances valued in a quote currency
+pub mod portfolio;
+/// Cancel and replace of orders
+pub mod replace;
+/// Report generation, download and parsing
+pub mod reports;
+/// Pre-trade risk checks
+pub mod risk;
+/// Product trading status from the status channel
+pub mod status;
+/// Tax lots and cost basis from account ledgers
+pub mod tax;
+/// Order lifecycle tracking over the user channel
+pub mod tracker;
+/// Trailing stop orders
+pub mod trailing;
 /// Public and private websocket feed
 pub mod websocket;
 
+pub use self::builder::Cursor;
 pub use self::client::{AuthenticatedClient, PublicClient};
-pub use self::paging::Pages;
+pub use self::paging::{Backfill, Direction, Items, Pages, Until};
 pub use self::websocket::WebSocketFeed;
\ No newline at end of file
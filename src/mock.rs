@@ -0,0 +1,122 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use reqwest::Url;
+
+/// Request received by a [serve](fn.serve.html) server
+#[derive(Debug, Clone)]
+pub(crate) struct Received {
+    pub method: String,
+    pub url: Url,
+    pub body: String,
+}
+
+impl Received {
+    /// Returns the value of a query parameter.
+    pub fn param(&self, name: &str) -> Option<String> {
+        self.url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned())
+    }
+
+    /// Returns the query string, empty without one.
+    pub fn query(&self) -> &str {
+        self.url.query().unwrap_or("")
+    }
+
+    /// Parses the body as json.
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+/// Json response of a [serve](fn.serve.html) server
+pub(crate) struct Reply {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Reply {
+    pub fn ok<B: ToString>(body: B) -> Self {
+        Self::status(200, body)
+    }
+
+    pub fn status<B: ToString>(status: u16, body: B) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    pub fn header<V: ToString>(mut self, name: &'static str, value: V) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+}
+
+/// Requests received so far, in order
+pub(crate) type Requests = Arc<Mutex<Vec<Received>>>;
+
+/// Answers every request with the reply of `handler` and closes the connection.
+/// Returns the base url of the server and the requests it received.
+pub(crate) fn serve<F>(mut handler: F) -> (Url, Requests)
+where
+    F: FnMut(&Received) -> Reply + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    let requests = Requests::default();
+    let recorded = requests.clone();
+    let url = base.clone();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let mut parts = line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let target = parts.next().unwrap_or("/").to_string();
+
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                let header = header.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                let mut pair = header.splitn(2, ':');
+                let name = pair.next().unwrap_or_default();
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = pair.next().unwrap_or_default().trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let received = Received {
+                method,
+                url: base.join(&target).unwrap(),
+                body: String::from_utf8(body).unwrap(),
+            };
+            let reply = handler(&received);
+            recorded.lock().unwrap().push(received);
+
+            let mut response = format!(
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                reply.status,
+                reply.body.len()
+            );
+            for (name, value) in reply.headers {
+                response += &format!("{}: {}\r\n", name, value);
+            }
+            response += "\r\n";
+            response += &reply.body;
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    (url, requests)
+}
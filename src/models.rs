@@ -0,0 +1,501 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Number {
+    Str(String),
+    Num(f64),
+}
+
+/// Coinbase sends prices and sizes as strings, accept both forms.
+pub(crate) fn number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    match Number::deserialize(deserializer)? {
+        Number::Str(value) => value.parse().map_err(serde::de::Error::custom),
+        Number::Num(value) => Ok(value),
+    }
+}
+
+/// Same as `number` for fields that may be missing or null, use together with `#[serde(default)]`.
+pub(crate) fn optional_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    match Option::<Number>::deserialize(deserializer)? {
+        Some(Number::Str(value)) => value.parse().map(Some).map_err(serde::de::Error::custom),
+        Some(Number::Num(value)) => Ok(Some(value)),
+        None => Ok(None),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Id {
+    Str(String),
+    Num(u64),
+}
+
+/// Ids are sent as strings or numbers depending on the endpoint, accept both forms.
+pub(crate) fn optional_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    match Option::<Id>::deserialize(deserializer)? {
+        Some(Id::Str(value)) => Ok(Some(value)),
+        Some(Id::Num(value)) => Ok(Some(value.to_string())),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    optional_id(deserializer)?.ok_or_else(|| serde::de::Error::custom("missing id"))
+}
+
+/// Fields of websocket messages used to track prices, orders and fills
+#[derive(Deserialize)]
+pub(crate) struct FeedMessage {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub product_id: Option<String>,
+    #[serde(default)]
+    pub order_id: Option<String>,
+    #[serde(default)]
+    pub client_oid: Option<String>,
+    #[serde(default)]
+    pub maker_order_id: Option<String>,
+    #[serde(default)]
+    pub taker_order_id: Option<String>,
+    #[serde(default)]
+    pub trade_id: Option<u64>,
+    #[serde(default)]
+    pub time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub side: Option<String>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub price: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub size: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub remaining_size: Option<f64>,
+    /// Set on `change` messages
+    #[serde(default, deserialize_with = "optional_number")]
+    pub new_size: Option<f64>,
+    /// `filled` or `canceled` on `done` messages
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub profile_id: Option<String>,
+    #[serde(default)]
+    pub taker_user_id: Option<String>,
+    #[serde(default)]
+    pub taker_profile_id: Option<String>,
+    /// Only sent to the taker on the `user` channel
+    #[serde(default, deserialize_with = "optional_number")]
+    pub taker_fee_rate: Option<f64>,
+    /// Set on `ticker` messages
+    #[serde(default, deserialize_with = "optional_number")]
+    pub best_bid: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub best_ask: Option<f64>,
+}
+
+impl FeedMessage {
+    /// Messages of the `user` channel carry the ids of the user and profile.
+    pub fn is_own(&self) -> bool {
+        self.user_id.is_some() || self.profile_id.is_some()
+    }
+
+    /// Whether the own order of a `match` message was the taker.
+    pub fn is_taker(&self) -> bool {
+        self.taker_user_id.is_some() || self.taker_profile_id.is_some()
+    }
+
+    /// Side of the own order of a `match` message, the side of a match is the maker side.
+    pub fn own_side(&self) -> &'static str {
+        match (self.side.as_deref(), self.is_taker()) {
+            (Some("buy"), false) | (Some("sell"), true) => "buy",
+            _ => "sell",
+        }
+    }
+}
+
+/// A single trade returned by `get_trades` or a `match` message of the websocket feed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub trade_id: u64,
+    pub time: DateTime<Utc>,
+    #[serde(deserialize_with = "number")]
+    pub price: f64,
+    #[serde(deserialize_with = "number")]
+    pub size: f64,
+    /// Side of the maker order
+    pub side: String,
+    /// Only present on websocket messages
+    #[serde(default)]
+    pub product_id: Option<String>,
+}
+
+/// Trading account of a profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub id: String,
+    pub currency: String,
+    #[serde(deserialize_with = "number")]
+    pub balance: f64,
+    #[serde(deserialize_with = "number")]
+    pub available: f64,
+    #[serde(deserialize_with = "number")]
+    pub hold: f64,
+    pub profile_id: String,
+    #[serde(default)]
+    pub trading_enabled: bool,
+}
+
+/// Profile the API key has access to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub active: bool,
+    pub is_default: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Accounts belonging to a single profile, see [list_all_accounts](../client/struct.AuthenticatedClient.html#method.list_all_accounts)
+#[derive(Debug, Clone)]
+pub struct ProfileAccounts {
+    pub profile: Profile,
+    pub accounts: Vec<Account>,
+}
+
+/// Result of a stablecoin conversion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversion {
+    pub id: String,
+    #[serde(deserialize_with = "number")]
+    pub amount: f64,
+    pub from_account_id: String,
+    pub to_account_id: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Report status returned by `create_report` and `get_report_status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// One of `pending`, `creating` or `ready`
+    pub status: String,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Set once the report is ready
+    #[serde(default)]
+    pub file_url: Option<String>,
+}
+
+/// Trading pair returned by `get_products`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Product {
+    pub id: String,
+    pub base_currency: String,
+    pub quote_currency: String,
+    #[serde(deserialize_with = "number")]
+    pub base_increment: f64,
+    #[serde(deserialize_with = "number")]
+    pub quote_increment: f64,
+    #[serde(default, deserialize_with = "number")]
+    pub base_min_size: f64,
+    #[serde(default, deserialize_with = "number")]
+    pub base_max_size: f64,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub cancel_only: bool,
+    #[serde(default)]
+    pub limit_only: bool,
+    #[serde(default)]
+    pub post_only: bool,
+    #[serde(default)]
+    pub trading_disabled: bool,
+}
+
+/// Snapshot returned by `get_product_ticker`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ticker {
+    pub trade_id: u64,
+    #[serde(deserialize_with = "number")]
+    pub price: f64,
+    #[serde(deserialize_with = "number")]
+    pub size: f64,
+    #[serde(deserialize_with = "number")]
+    pub bid: f64,
+    #[serde(deserialize_with = "number")]
+    pub ask: f64,
+    #[serde(deserialize_with = "number")]
+    pub volume: f64,
+    pub time: DateTime<Utc>,
+}
+
+/// Fill returned by `get_fills`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fill {
+    pub trade_id: u64,
+    pub product_id: String,
+    #[serde(default)]
+    pub order_id: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(deserialize_with = "number")]
+    pub price: f64,
+    #[serde(deserialize_with = "number")]
+    pub size: f64,
+    #[serde(deserialize_with = "number")]
+    pub fee: f64,
+    /// Side of the own order
+    pub side: String,
+    /// `M` for maker or `T` for taker
+    #[serde(default)]
+    pub liquidity: String,
+    #[serde(default)]
+    pub settled: bool,
+}
+
+/// Entry of `get_account_history`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    #[serde(deserialize_with = "id")]
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    /// Signed change of the balance
+    #[serde(deserialize_with = "number")]
+    pub amount: f64,
+    #[serde(deserialize_with = "number")]
+    pub balance: f64,
+    /// One of `transfer`, `match`, `fee`, `rebate` or `conversion`
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub details: LedgerDetails,
+}
+
+/// Origin of a ledger entry, the fields set depend on the entry type
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LedgerDetails {
+    #[serde(default)]
+    pub order_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub trade_id: Option<String>,
+    #[serde(default)]
+    pub product_id: Option<String>,
+    #[serde(default)]
+    pub transfer_id: Option<String>,
+    /// `deposit` or `withdraw`
+    #[serde(default)]
+    pub transfer_type: Option<String>,
+    #[serde(default)]
+    pub conversion_id: Option<String>,
+}
+
+/// Order returned by `get_order`, `list_orders` and the place order methods
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub id: String,
+    #[serde(default)]
+    pub client_oid: Option<String>,
+    pub product_id: String,
+    pub side: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub price: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub size: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub funds: Option<f64>,
+    /// One of `pending`, `open`, `active` or `done`
+    pub status: String,
+    /// `filled` or `canceled` once done
+    #[serde(default)]
+    pub done_reason: Option<String>,
+    #[serde(default, deserialize_with = "number")]
+    pub filled_size: f64,
+    /// Quote amount of all fills excluding fees
+    #[serde(default, deserialize_with = "number")]
+    pub executed_value: f64,
+    #[serde(default, deserialize_with = "number")]
+    pub fill_fees: f64,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub done_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub settled: bool,
+}
+
+/// Price and size of a level of the order book
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PriceLevel(
+    #[serde(deserialize_with = "number")] pub f64,
+    #[serde(deserialize_with = "number")] pub f64,
+);
+
+/// Side, price and new size of a level in a `l2update` message, a size of 0 removes the level
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct L2Change(
+    pub String,
+    #[serde(deserialize_with = "number")] pub f64,
+    #[serde(deserialize_with = "number")] pub f64,
+);
+
+/// Order book sent first on the `level2` and `level2_batch` channels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct L2Snapshot {
+    pub product_id: String,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+/// Changes to the order book on the `level2` and `level2_batch` channels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct L2Update {
+    pub product_id: String,
+    #[serde(default)]
+    pub time: Option<DateTime<Utc>>,
+    pub changes: Vec<L2Change>,
+}
+
+/// Message of the `ticker` and `ticker_batch` channels, trade fields are missing on the first message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickerMessage {
+    pub product_id: String,
+    #[serde(default)]
+    pub sequence: Option<u64>,
+    #[serde(default)]
+    pub trade_id: Option<u64>,
+    #[serde(deserialize_with = "number")]
+    pub price: f64,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub best_bid: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub best_ask: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub last_size: Option<f64>,
+    /// Side of the taker
+    #[serde(default)]
+    pub side: Option<String>,
+    #[serde(default)]
+    pub time: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub open_24h: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub high_24h: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub low_24h: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub volume_24h: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub volume_30d: Option<f64>,
+}
+
+/// Message of the `auctionfeed` channel while a product is in auction mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuctionMessage {
+    pub product_id: String,
+    #[serde(default)]
+    pub sequence: Option<u64>,
+    /// e.g. `collection` or `opening`
+    pub auction_state: String,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub best_bid_price: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub best_bid_size: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub best_ask_price: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub best_ask_size: Option<f64>,
+    /// Indicative open price and size
+    #[serde(default, deserialize_with = "optional_number")]
+    pub open_price: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub open_size: Option<f64>,
+    /// `yes` once the book can open
+    #[serde(default)]
+    pub can_open: Option<String>,
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// Product of a `status` message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusProduct {
+    pub id: String,
+    pub base_currency: String,
+    pub quote_currency: String,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub base_increment: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub quote_increment: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub base_min_size: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub base_max_size: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub min_market_funds: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub max_market_funds: Option<f64>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// e.g. `online`, `offline` or `delisted`
+    pub status: String,
+    #[serde(default)]
+    pub status_message: Option<String>,
+    #[serde(default)]
+    pub post_only: bool,
+    #[serde(default)]
+    pub limit_only: bool,
+    #[serde(default)]
+    pub cancel_only: bool,
+    #[serde(default)]
+    pub auction_mode: bool,
+}
+
+/// Currency of a `status` message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusCurrency {
+    pub id: String,
+    pub name: String,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub min_size: Option<f64>,
+    pub status: String,
+    #[serde(default)]
+    pub status_message: Option<String>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub max_precision: Option<f64>,
+    #[serde(default)]
+    pub convertible_to: Vec<String>,
+}
+
+/// Message of the `status` channel with all products and currencies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusMessage {
+    #[serde(default)]
+    pub products: Vec<StatusProduct>,
+    #[serde(default)]
+    pub currencies: Vec<StatusCurrency>,
+}
+
+/// Typed message of the `level2`, `level2_batch`, `ticker`, `ticker_batch`, `auctionfeed` and `status` channels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ChannelMessage {
+    Snapshot(L2Snapshot),
+    L2update(L2Update),
+    Ticker(TickerMessage),
+    Auction(AuctionMessage),
+    Status(StatusMessage),
+    /// Messages of other channels
+    #[serde(other)]
+    Other,
+}
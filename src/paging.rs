@@ -1,14 +1,19 @@
 use core::pin::Pin;
+use core::time::Duration;
+use std::collections::VecDeque;
+use chrono::{DateTime, Utc};
 use futures::{
     future::{BoxFuture, FutureExt},
-    stream::{BoxStream, Stream, StreamExt},
+    stream::{BoxStream, Stream, StreamExt, TryStreamExt},
     task::{Context, Poll},
 };
-use reqwest::{Response, Client, Request};
+use reqwest::{header::HeaderMap, Client, Request};
 use serde::de::DeserializeOwned;
 use crate::{
-    builder::{ Paginate, apply_query, Params },
-    error::{ Error, CBError, Kind }
+    builder::{ CBParams, Cursor, Paginate, apply_params, sign_request, Params, TradeOptions },
+    client::Auth,
+    error::{CBError, Error},
+    models::Trade,
 };
 
 enum State {
@@ -16,78 +21,810 @@ enum State {
     Stop,
 }
 
-type ResponseFuture = BoxFuture<'static, Result<Response, reqwest::Error>>;
-/// Alias representing a stream of json pages
-pub type Pages<'a, J> = BoxStream<'a, crate::error::Result<J>>;
+/// Order in which pages are walked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// Follows the `cb-after` cursor towards older items
+    Older,
+    /// Follows the `cb-before` cursor towards newer items
+    Newer,
+}
+
+#[derive(Clone, Copy, Default)]
+pub(super) struct Paging {
+    pub direction: Option<Direction>,
+    pub tail: Option<Duration>,
+}
+
+enum Next {
+    Fetch,
+    Wait(Duration),
+    Stop,
+}
+
+struct Page<B> {
+    body: B,
+    before: Option<Cursor>,
+    after: Option<Cursor>,
+}
+
+type PageFuture<B> = BoxFuture<'static, crate::error::Result<Page<B>>>;
+
+fn cursor(headers: &HeaderMap, name: &str) -> crate::error::Result<Option<Cursor>> {
+    match headers.get(name) {
+        Some(value) => Ok(Some(Cursor::from(value.to_str()?))),
+        None => Ok(None),
+    }
+}
 
-pub(super) struct Paginated<T> {
-    in_flight: ResponseFuture,
+fn fetch<B>(client: &Client, request: &Request, params: &CBParams, auth: Option<&Auth>, delay: Option<Duration>) -> PageFuture<B>
+where
+    B: DeserializeOwned + Send + 'static,
+{
+    let client = client.clone();
+    let auth = auth.cloned();
+    let mut request = request.try_clone().unwrap();
+    let applied = apply_params(&mut request, params);
+    async move {
+        applied?;
+        if let Some(delay) = delay {
+            tokio::time::delay_for(delay).await;
+        }
+        // signed after the delay so the timestamp is fresh
+        if let Some(ref auth) = auth {
+            sign_request(&mut request, auth)?;
+        }
+
+        let resp = client.execute(request).await?;
+        if resp.status().is_success() {
+            let before = cursor(resp.headers(), "cb-before")?;
+            let after = cursor(resp.headers(), "cb-after")?;
+            let body = resp.json::<B>().await?;
+            Ok(Page { body, before, after })
+        } else {
+            let error = CBError::new(resp.status().as_u16(), resp.text().await?);
+            Err(error.into())
+        }
+    }.boxed()
+}
+
+trait PaginatedQuery<'a>: Params<'a> + Paginate<'a> + Send {}
+
+impl<'a, T: Params<'a> + Paginate<'a> + Send> PaginatedQuery<'a> for T {}
+
+/// Walks pages in one direction and stops on an empty page or a missing cursor.
+/// In tail mode the newer direction keeps polling from the last cursor instead of stopping.
+struct Pager<'a> {
     client: Client,
     request: Request,
-    query: T,
-    state: State
+    query: Box<dyn PaginatedQuery<'a> + 'a>,
+    auth: Option<Auth>,
+    direction: Direction,
+    tail: Option<Duration>,
+    before: Option<Cursor>,
+    after: Option<Cursor>,
+    pages: usize,
 }
 
-impl<'a, T: Params<'a> + Paginate<'a> + Send + Unpin + 'a> Paginated<T> {
-    pub(super) fn new(client: Client, request: Request, query: T) -> Self {
+impl<'a> Pager<'a> {
+    fn new<Q: Params<'a> + Paginate<'a> + Send + 'a>(client: Client, request: Request, query: Q, auth: Option<Auth>, paging: Paging) -> Self {
+        let direction = match (paging.direction, &query.params().before) {
+            (Some(direction), _) => direction,
+            (None, Some(_)) => Direction::Newer,
+            (None, None) => Direction::Older,
+        };
         Self {
-            in_flight: client.execute(request.try_clone().unwrap()).boxed(),
             client,
             request,
-            query,
+            query: Box::new(query),
+            auth,
+            direction,
+            tail: paging.tail,
+            before: None,
+            after: None,
+            pages: 0,
+        }
+    }
+
+    fn fetch<B: DeserializeOwned + Send + 'static>(&self, delay: Option<Duration>) -> PageFuture<B> {
+        fetch(&self.client, &self.request, self.query.params(), self.auth.as_ref(), delay)
+    }
+
+    /// Records the cursors of a received page and moves the query to the next one.
+    fn advance(&mut self, before: Option<Cursor>, after: Option<Cursor>, empty: bool) -> Next {
+        self.pages += 1;
+        let (has_before, next_after) = (before.is_some(), after.clone());
+        // keep the newest before and the oldest after cursor whichever way the pages are walked
+        if before.is_some() && (self.direction == Direction::Newer || self.before.is_none()) {
+            self.before = before;
+        }
+        if after.is_some() && (self.direction == Direction::Older || self.after.is_none()) {
+            self.after = after;
+        }
+
+        // only the cursor of this page moves the walk on, reusing an older one would refetch the same page
+        match self.direction {
+            Direction::Older => match (empty, next_after) {
+                (false, Some(after)) => {
+                    self.query.set_after(after);
+                    Next::Fetch
+                }
+                _ => Next::Stop,
+            },
+            Direction::Newer => {
+                if let Some(before) = self.before.clone() {
+                    self.query.set_before(before);
+                }
+                match (empty || !has_before, self.tail) {
+                    (false, _) => Next::Fetch,
+                    (true, Some(interval)) => Next::Wait(interval),
+                    (true, None) => Next::Stop,
+                }
+            }
+        }
+    }
+}
+
+/// Alias representing a stream of json pages
+pub type Pages<'a, J> = BoxStream<'a, crate::error::Result<J>>;
+
+pub(super) struct Paginated<'a> {
+    pager: Pager<'a>,
+    in_flight: Option<PageFuture<serde_json::Value>>,
+    state: State,
+}
+
+impl<'a> Paginated<'a> {
+    pub(super) fn new<Q: Params<'a> + Paginate<'a> + Send + 'a>(client: Client, request: Request, query: Q, auth: Option<Auth>, paging: Paging) -> Self {
+        let pager = Pager::new(client, request, query, auth, paging);
+        Self {
+            in_flight: Some(pager.fetch(None)),
+            pager,
             state: State::Start,
         }
     }
 
     pub(super) fn pages<J: DeserializeOwned>(self) -> Pages<'a, J> {
-        self.then(|res| async move { 
-            let resp = res?;
-            if resp.status().is_success() {
-                Ok(resp.json::<J>().await?)
-            } else {
-                let error = CBError::new(resp.status().as_u16(), resp.text().await?);
-                Err(error.into())
+        self.and_then(|json| async move { Ok(serde_json::from_value(json)?) }).boxed()
+    }
+}
+
+impl<'a> Unpin for Paginated<'a> {}
+
+impl<'a> Stream for Paginated<'a> {
+    type Item = crate::error::Result<serde_json::Value>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let State::Stop = self.state {
+                return Poll::Ready(None);
+            }
+
+            let page = match self.in_flight.as_mut().unwrap().poll_unpin(cx) {
+                Poll::Ready(Ok(page)) => page,
+                Poll::Ready(Err(e)) => {
+                    self.state = State::Stop;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let empty = matches!(page.body.as_array(), Some(items) if items.is_empty());
+            match self.pager.advance(page.before, page.after, empty) {
+                Next::Fetch => self.in_flight = Some(self.pager.fetch(None)),
+                Next::Wait(interval) => self.in_flight = Some(self.pager.fetch(Some(interval))),
+                Next::Stop => self.state = State::Stop,
             }
-             
-        }).boxed()
+
+            // empty pages are only produced while tailing and carry nothing new
+            if !empty {
+                return Poll::Ready(Some(Ok(page.body)));
+            }
+        }
+    }
+}
+
+/// Target at which a trade backfill stops
+#[derive(Debug, Clone, Copy)]
+pub enum Until {
+    /// Stops after yielding the trade with this id
+    TradeID(u64),
+    /// Stops before the first trade older than this time
+    Time(DateTime<Utc>),
+}
+
+impl Until {
+    fn reached(&self, trade: &Trade) -> bool {
+        match *self {
+            Until::TradeID(id) => trade.trade_id < id,
+            Until::Time(time) => trade.time < time,
+        }
+    }
+}
+
+/// Stream of trades returned by [backfill](builder/struct.QueryBuilder.html#method.backfill), newest first.
+pub struct Backfill<'a> {
+    client: Client,
+    request: Request,
+    query: TradeOptions<'a>,
+    auth: Option<Auth>,
+    until: Until,
+    in_flight: Option<PageFuture<Vec<Trade>>>,
+    trades: VecDeque<Trade>,
+    cursor: Option<Cursor>,
+    error: Option<Error>,
+    state: State,
+}
+
+impl<'a> Backfill<'a> {
+    pub(super) fn new(client: Client, request: Request, query: TradeOptions<'a>, auth: Option<Auth>, until: Until) -> Self {
+        let cursor = query.params().after.clone();
+        let mut backfill = Self {
+            client,
+            request,
+            query,
+            auth,
+            until,
+            in_flight: None,
+            trades: VecDeque::new(),
+            cursor,
+            error: None,
+            state: State::Start,
+        };
+        backfill.in_flight = Some(backfill.fetch());
+        backfill
+    }
+
+    /// Makes the first poll return the error without sending a request.
+    pub(super) fn fail(mut self, error: Error) -> Self {
+        self.in_flight = None;
+        self.error = Some(error);
+        self
+    }
+
+    /// Returns the `after` cursor of the last yielded trade. 
+    /// Passing it to `after` resumes the backfill without skipping or repeating trades.
+    pub fn cursor(&self) -> Option<&Cursor> {
+        self.cursor.as_ref()
+    }
+
+    fn fetch(&self) -> PageFuture<Vec<Trade>> {
+        fetch(&self.client, &self.request, self.query.params(), self.auth.as_ref(), None)
+    }
+}
+
+impl<'a> Stream for Backfill<'a> {
+    type Item = crate::error::Result<Trade>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(error) = self.error.take() {
+            self.state = State::Stop;
+            return Poll::Ready(Some(Err(error)));
+        }
+
+        loop {
+            if let State::Stop = self.state {
+                return Poll::Ready(None);
+            }
+
+            if let Some(trade) = self.trades.pop_front() {
+                if self.until.reached(&trade) {
+                    self.state = State::Stop;
+                    return Poll::Ready(None);
+                }
+                self.cursor = Some(Cursor::from(trade.trade_id));
+                return Poll::Ready(Some(Ok(trade)));
+            }
+
+            let in_flight = match self.in_flight {
+                Some(ref mut in_flight) => in_flight,
+                None => {
+                    match self.cursor.clone() {
+                        Some(cursor) => self.query.set_after(cursor),
+                        None => {
+                            self.state = State::Stop;
+                            continue;
+                        }
+                    }
+                    let in_flight = self.fetch();
+                    self.in_flight.get_or_insert(in_flight)
+                }
+            };
+
+            let trades = match in_flight.poll_unpin(cx) {
+                Poll::Ready(Ok(page)) => page.body,
+                Poll::Ready(Err(e)) => {
+                    self.state = State::Stop;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+            self.in_flight = None;
+
+            if trades.is_empty() {
+                self.state = State::Stop;
+            }
+            self.trades.extend(trades);
+        }
+    }
+}
+
+type Predicate<'a, T> = Box<dyn FnMut(&T) -> bool + Send + 'a>;
+
+/// Stream of items returned by [paginate_items](builder/struct.QueryBuilder.html#method.paginate_items). 
+/// Pages are fetched as the stream is consumed.
+pub struct Items<'a, T> {
+    pager: Pager<'a>,
+    in_flight: Option<PageFuture<Vec<T>>>,
+    items: VecDeque<T>,
+    yielded: usize,
+    max_pages: Option<usize>,
+    max_items: Option<usize>,
+    until: Option<Predicate<'a, T>>,
+    state: State,
+}
+
+impl<'a, T> Unpin for Items<'a, T> {}
+
+impl<'a, T: DeserializeOwned + Send + 'static> Items<'a, T> {
+    pub(super) fn new<Q: Params<'a> + Paginate<'a> + Send + 'a>(client: Client, request: Request, query: Q, auth: Option<Auth>, paging: Paging) -> Self {
+        let pager = Pager::new(client, request, query, auth, paging);
+        Self {
+            in_flight: Some(pager.fetch(None)),
+            pager,
+            items: VecDeque::new(),
+            yielded: 0,
+            max_pages: None,
+            max_items: None,
+            until: None,
+            state: State::Start,
+        }
+    }
+
+    /// Stops after the given number of pages has been fetched.
+    pub fn max_pages(mut self, value: usize) -> Self {
+        self.max_pages = Some(value);
+        self
+    }
+
+    /// Stops after the given number of items has been yielded.
+    pub fn max_items(mut self, value: usize) -> Self {
+        self.max_items = Some(value);
+        self
+    }
+
+    /// Stops before the first item for which the predicate returns true.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{PublicClient, SANDBOX_URL};
+    /// use cbpro::models::Trade;
+    /// use futures::TryStreamExt;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = PublicClient::new(SANDBOX_URL);
+    /// let start = chrono::offset::Utc::now() - chrono::Duration::hours(1);
+    /// let mut trades = client.get_trades("BTC-USD")
+    ///     .paginate_items::<Trade>()?
+    ///     .max_items(5000)
+    ///     .until(move |trade| trade.time < start);
+    ///
+    /// while let Some(trade) = trades.try_next().await? {
+    ///     println!("{:?}", trade);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn until<F: FnMut(&T) -> bool + Send + 'a>(mut self, predicate: F) -> Self {
+        self.until = Some(Box::new(predicate));
+        self
+    }
+
+    /// Returns the newest `cb-before` cursor seen so far.
+    pub fn before(&self) -> Option<&Cursor> {
+        self.pager.before.as_ref()
+    }
+
+    /// Returns the oldest `cb-after` cursor seen so far.
+    pub fn after(&self) -> Option<&Cursor> {
+        self.pager.after.as_ref()
     }
 }
 
-impl<'a, T: Params<'a> + Paginate<'a> + Send + Unpin+ 'a> Stream for Paginated<T> {
-    type Item = crate::error::Result<Response>;
+impl<'a, T: DeserializeOwned + Send + 'static> Stream for Items<'a, T> {
+    type Item = crate::error::Result<T>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if let State::Stop = self.state {
-            return Poll::Ready(None);
+        loop {
+            if let State::Stop = self.state {
+                return Poll::Ready(None);
+            }
+
+            if let Some(max) = self.max_items {
+                if self.yielded >= max {
+                    self.state = State::Stop;
+                    continue;
+                }
+            }
+
+            if let Some(item) = self.items.pop_front() {
+                if let Some(ref mut until) = self.until {
+                    if until(&item) {
+                        self.state = State::Stop;
+                        continue;
+                    }
+                }
+                self.yielded += 1;
+                return Poll::Ready(Some(Ok(item)));
+            }
+
+            let max_pages = matches!(self.max_pages, Some(max) if self.pager.pages >= max);
+            if max_pages || self.in_flight.is_none() {
+                self.state = State::Stop;
+                continue;
+            }
+
+            let page = match self.in_flight.as_mut().unwrap().poll_unpin(cx) {
+                Poll::Ready(Ok(page)) => page,
+                Poll::Ready(Err(e)) => {
+                    self.state = State::Stop;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let empty = page.body.is_empty();
+            self.in_flight = match self.pager.advance(page.before, page.after, empty) {
+                Next::Fetch => Some(self.pager.fetch(None)),
+                Next::Wait(interval) => Some(self.pager.fetch(Some(interval))),
+                Next::Stop => None,
+            };
+            self.items.extend(page.body);
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{header::HeaderValue, Method, Url};
+    use crate::builder::{PageOptions, QueryBuilder};
+    use crate::error::Kind;
+    use crate::mock::{self, Reply, Requests};
 
-        let res = match self.as_mut().in_flight.poll_unpin(cx) {
-            Poll::Ready(Err(e)) => {
-                return Poll::Ready(Some(Err(Error::new(Kind::Reqwest, Some(e)))));
+    type Handler = fn(Option<&str>, Option<&str>) -> (Vec<(&'static str, String)>, String);
+
+    /// Serves one json page per request, cursors are taken from the before and after params.
+    fn serve(handler: Handler) -> (Url, Requests) {
+        let (url, requests) = mock::serve(move |request| {
+            let (headers, body) = handler(request.param("before").as_deref(), request.param("after").as_deref());
+            headers.into_iter().fold(Reply::ok(body), |reply, (name, value)| reply.header(name, value))
+        });
+        (url.join("/items").unwrap(), requests)
+    }
+
+    /// Items 5 to 1 newest first, two per page, cursors are the first and last id of a page.
+    fn numbered(before: Option<&str>, after: Option<&str>) -> (Vec<(&'static str, String)>, String) {
+        let all: Vec<u64> = (1..=5).rev().collect();
+        let page: Vec<u64> = match (before, after) {
+            (Some(before), _) => {
+                let before: u64 = before.parse().unwrap();
+                let newer: Vec<u64> = all.iter().copied().filter(|id| *id > before).collect();
+                newer[newer.len().saturating_sub(2)..].to_vec()
             }
-            Poll::Ready(Ok(res)) => res,
-            Poll::Pending => return Poll::Pending,
+            (None, Some(after)) => {
+                let after: u64 = after.parse().unwrap();
+                all.iter().copied().filter(|id| *id < after).take(2).collect()
+            }
+            (None, None) => all.iter().copied().take(2).collect(),
         };
 
-        if let (Some(after), None) = (res.headers().get("cb-after"), self.query.params().before) {
-            self.as_mut().query.set_after(after.to_str()?.parse().unwrap());
-            let mut request = self.request.try_clone().unwrap();
-            request.url_mut().set_query(None);
+        let mut headers = Vec::new();
+        if let (Some(first), Some(last)) = (page.first(), page.last()) {
+            headers.push(("cb-before", first.to_string()));
+            headers.push(("cb-after", last.to_string()));
+        }
+        (headers, serde_json::to_string(&page).unwrap())
+    }
 
-            apply_query(&mut request, self.query.params())?;
-            self.as_mut().in_flight = self.client.execute(request).boxed()
+    fn items(url: Url, query: PageOptions<'static>, paging: Paging) -> Items<'static, u64> {
+        Items::new(Client::new(), Request::new(Method::GET, url), query, None, paging)
+    }
 
-        } else if let Some(before) = res.headers().get("cb-before") {
-            self.as_mut().query.set_before(before.to_str()?.parse().unwrap());
-            let mut request = self.request.try_clone().unwrap();
-            request.url_mut().set_query(None);
+    fn queries(requests: &Requests) -> Vec<String> {
+        requests.lock().unwrap().iter().map(|request| request.query().to_string()).collect()
+    }
 
-            apply_query(&mut request, self.query.params())?;
-            self.as_mut().in_flight = self.client.execute(request).boxed()
+    #[tokio::test]
+    async fn items_walk_all_pages_until_empty_page() {
+        let (url, recorded) = serve(numbered);
+        let mut stream = items(url, PageOptions::new(), Paging::default());
 
-        } else {
-            self.as_mut().state = State::Stop;
+        let mut ids = Vec::new();
+        while let Some(id) = stream.try_next().await.unwrap() {
+            ids.push(id);
+        }
+        assert_eq!(ids, vec![5, 4, 3, 2, 1]);
+        assert_eq!(queries(&recorded), vec!["", "after=4", "after=2", "after=1"]);
+        assert_eq!(stream.after(), Some(&Cursor::from(1u64)));
+        assert_eq!(stream.before(), Some(&Cursor::from(5u64)));
+    }
+
+    #[tokio::test]
+    async fn items_max_items_stops_without_fetching_more() {
+        let (url, recorded) = serve(numbered);
+        let ids: Vec<u64> = items(url, PageOptions::new(), Paging::default())
+            .max_items(3)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(ids, vec![5, 4, 3]);
+        assert_eq!(queries(&recorded), vec!["", "after=4"]);
+    }
+
+    #[tokio::test]
+    async fn items_max_pages_yields_whole_pages() {
+        let (url, recorded) = serve(numbered);
+        let ids: Vec<u64> = items(url, PageOptions::new(), Paging::default())
+            .max_pages(2)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(ids, vec![5, 4, 3, 2]);
+        assert_eq!(queries(&recorded), vec!["", "after=4"]);
+    }
+
+    #[tokio::test]
+    async fn items_until_stops_before_matching_item() {
+        let (url, _) = serve(numbered);
+        let ids: Vec<u64> = items(url, PageOptions::new(), Paging::default())
+            .until(|id| *id < 3)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(ids, vec![5, 4, 3]);
+    }
+
+    /// One item behind a cursor that does not fit into a u64.
+    fn opaque(_: Option<&str>, after: Option<&str>) -> (Vec<(&'static str, String)>, String) {
+        match after {
+            None => (vec![("cb-after", "18446744073709551616".to_string())], "[1]".to_string()),
+            Some(_) => (vec![], "[]".to_string()),
+        }
+    }
+
+    #[test]
+    fn cursor_headers_are_kept_verbatim() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cb-before", HeaderValue::from_static("2020-03-01T00:00:00.123456Z"));
+        headers.insert("cb-after", HeaderValue::from_static("18446744073709551616"));
+
+        let before = cursor(&headers, "cb-before").unwrap().unwrap();
+        assert_eq!(before.as_str(), "2020-03-01T00:00:00.123456Z");
+        assert!(before.as_u64().is_err());
+
+        let after = cursor(&headers, "cb-after").unwrap().unwrap();
+        assert_eq!(after.to_string(), "18446744073709551616");
+        assert!(after.as_u64().is_err());
+
+        assert_eq!(cursor(&headers, "cb-missing").unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_cursor_header_is_an_error() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cb-after", HeaderValue::from_bytes(b"caf\xc3\xa9").unwrap());
+        assert!(cursor(&headers, "cb-after").is_err());
+    }
+
+    #[tokio::test]
+    async fn large_cursor_is_sent_back_unchanged() {
+        let (url, recorded) = serve(opaque);
+        let mut stream = items(url, PageOptions::new(), Paging::default());
+
+        assert_eq!(stream.try_next().await.unwrap(), Some(1));
+        assert_eq!(stream.try_next().await.unwrap(), None);
+        assert_eq!(queries(&recorded), vec!["", "after=18446744073709551616"]);
+    }
+
+    fn new_pager(query: PageOptions<'static>, paging: Paging) -> Pager<'static> {
+        let url = Url::parse("http://localhost/items").unwrap();
+        Pager::new(Client::new(), Request::new(Method::GET, url), query, None, paging)
+    }
+
+    fn page_cursors(pager: &Pager) -> (Option<String>, Option<String>) {
+        let params = pager.query.params();
+        (params.before.as_ref().map(Cursor::to_string), params.after.as_ref().map(Cursor::to_string))
+    }
+
+    fn some(cursor: &str) -> Option<Cursor> {
+        Some(Cursor::from(cursor))
+    }
+
+    #[test]
+    fn pager_direction_defaults_to_query_cursor() {
+        assert_eq!(new_pager(PageOptions::new(), Paging::default()).direction, Direction::Older);
+
+        let mut query = PageOptions::new();
+        query.set_before(Cursor::from(10u64));
+        assert_eq!(new_pager(query, Paging::default()).direction, Direction::Newer);
+
+        let mut query = PageOptions::new();
+        query.set_before(Cursor::from(10u64));
+        let paging = Paging { direction: Some(Direction::Older), tail: None };
+        assert_eq!(new_pager(query, paging).direction, Direction::Older);
+    }
+
+    #[test]
+    fn pager_older_follows_after_cursor() {
+        let mut pager = new_pager(PageOptions::new(), Paging::default());
+
+        assert!(matches!(pager.advance(some("5"), some("4"), false), Next::Fetch));
+        assert_eq!(page_cursors(&pager), (None, Some("4".to_string())));
+
+        assert!(matches!(pager.advance(some("3"), some("2"), false), Next::Fetch));
+        assert_eq!(page_cursors(&pager), (None, Some("2".to_string())));
+        assert_eq!(pager.before, some("5"));
+        assert_eq!(pager.after, some("2"));
+        assert_eq!(pager.pages, 2);
+    }
+
+    #[test]
+    fn pager_older_stops_on_empty_page_or_missing_cursor() {
+        let mut pager = new_pager(PageOptions::new(), Paging::default());
+        assert!(matches!(pager.advance(None, None, true), Next::Stop));
+
+        // tail mode only applies to the newer direction
+        let paging = Paging { direction: None, tail: Some(Duration::from_secs(1)) };
+        let mut pager = new_pager(PageOptions::new(), paging);
+        assert!(matches!(pager.advance(some("5"), some("4"), false), Next::Fetch));
+        assert!(matches!(pager.advance(some("3"), None, false), Next::Stop));
+        assert_eq!(page_cursors(&pager), (None, Some("4".to_string())));
+    }
+
+    #[test]
+    fn pager_newer_follows_before_cursor() {
+        let mut query = PageOptions::new();
+        query.set_before(Cursor::from(1u64));
+        let mut pager = new_pager(query, Paging::default());
+
+        assert!(matches!(pager.advance(some("3"), some("2"), false), Next::Fetch));
+        assert_eq!(page_cursors(&pager), (Some("3".to_string()), None));
+
+        assert!(matches!(pager.advance(some("5"), some("4"), false), Next::Fetch));
+        assert_eq!(page_cursors(&pager), (Some("5".to_string()), None));
+        assert_eq!(pager.after, some("2"));
+
+        assert!(matches!(pager.advance(None, None, true), Next::Stop));
+        assert!(matches!(pager.advance(some("6"), None, false), Next::Fetch));
+        assert!(matches!(pager.advance(None, None, false), Next::Stop));
+    }
+
+    #[test]
+    fn pager_tail_waits_on_last_cursor() {
+        let interval = Duration::from_millis(500);
+        let mut query = PageOptions::new();
+        query.set_before(Cursor::from(1u64));
+        let mut pager = new_pager(query, Paging { direction: None, tail: Some(interval) });
+
+        // nothing newer yet, keeps polling from the cursor of the first page
+        assert!(matches!(pager.advance(None, None, true), Next::Wait(wait) if wait == interval));
+        assert_eq!(page_cursors(&pager), (Some("1".to_string()), None));
+
+        assert!(matches!(pager.advance(some("3"), some("2"), false), Next::Fetch));
+        assert!(matches!(pager.advance(None, None, true), Next::Wait(_)));
+        assert_eq!(page_cursors(&pager), (Some("3".to_string()), None));
+    }
+
+    #[tokio::test]
+    async fn pages_newer_stop_on_empty_page() {
+        let (url, recorded) = serve(numbered);
+        let mut query = PageOptions::new();
+        query.set_before(Cursor::from(1u64));
+
+        let pages: Vec<Vec<u64>> = Paginated::new(Client::new(), Request::new(Method::GET, url), query, None, Paging::default())
+            .pages()
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(pages, vec![vec![3, 2], vec![5, 4]]);
+        assert_eq!(queries(&recorded), vec!["before=1", "before=3", "before=5"]);
+    }
+
+    /// Trades 5 to 1 newest first, one second apart, two per page.
+    fn trades(_: Option<&str>, after: Option<&str>) -> (Vec<(&'static str, String)>, String) {
+        let after: u64 = after.map_or(6, |after| after.parse().unwrap());
+        let page: Vec<serde_json::Value> = (1..after)
+            .rev()
+            .take(2)
+            .map(|id| {
+                serde_json::json!({
+                    "trade_id": id,
+                    "time": format!("2020-01-01T00:00:0{}Z", id),
+                    "price": "100.00",
+                    "size": "1.0",
+                    "side": "buy",
+                })
+            })
+            .collect();
+
+        let mut headers = Vec::new();
+        if let Some(last) = page.last() {
+            headers.push(("cb-after", last["trade_id"].to_string()));
         }
-        Poll::Ready(Some(Ok(res)))
+        (headers, serde_json::to_string(&page).unwrap())
+    }
+
+    fn backfill(url: Url, query: TradeOptions<'static>, until: Until) -> Backfill<'static> {
+        Backfill::new(Client::new(), Request::new(Method::GET, url), query, None, until)
+    }
+
+    async fn trade_ids(backfill: &mut Backfill<'_>) -> Vec<u64> {
+        let mut ids = Vec::new();
+        while let Some(trade) = backfill.try_next().await.unwrap() {
+            ids.push(trade.trade_id);
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn backfill_stops_after_trade_id() {
+        let (url, recorded) = serve(trades);
+        let mut backfill = backfill(url, TradeOptions::new(), Until::TradeID(3));
+
+        assert_eq!(trade_ids(&mut backfill).await, vec![5, 4, 3]);
+        assert_eq!(backfill.cursor(), Some(&Cursor::from(3u64)));
+        assert_eq!(queries(&recorded), vec!["", "after=4"]);
+    }
+
+    #[tokio::test]
+    async fn backfill_stops_before_older_time() {
+        let (url, _) = serve(trades);
+        let time = "2020-01-01T00:00:02Z".parse().unwrap();
+        let mut backfill = backfill(url, TradeOptions::new(), Until::Time(time));
+
+        assert_eq!(trade_ids(&mut backfill).await, vec![5, 4, 3, 2]);
+        assert_eq!(backfill.cursor(), Some(&Cursor::from(2u64)));
+    }
+
+    #[tokio::test]
+    async fn backfill_resumes_from_cursor_and_ends_on_empty_page() {
+        let (url, recorded) = serve(trades);
+        let mut query = TradeOptions::new();
+        query.set_after(Cursor::from(4u64));
+        let mut backfill = backfill(url, query, Until::TradeID(0));
+
+        assert_eq!(trade_ids(&mut backfill).await, vec![3, 2, 1]);
+        assert_eq!(queries(&recorded), vec!["after=4", "after=2", "after=1"]);
+    }
+
+    #[tokio::test]
+    async fn backfill_returns_builder_error() {
+        let (url, recorded) = serve(trades);
+        let error = Error::new(Kind::Conversion, Some("invalid query"));
+        let mut backfill = QueryBuilder::new(Client::new(), Request::new(Method::GET, url), TradeOptions::new(), None)
+            .fail(error)
+            .backfill(Until::TradeID(0));
+
+        assert!(backfill.try_next().await.is_err());
+        assert!(backfill.try_next().await.unwrap().is_none());
+        assert!(queries(&recorded).is_empty());
+    }
+
+    #[tokio::test]
+    async fn items_zero_limit_yields_nothing() {
+        let (url, recorded) = serve(numbered);
+        let ids: Vec<u64> = items(url, PageOptions::new(), Paging::default())
+            .max_items(0)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert!(ids.is_empty());
+        assert!(queries(&recorded).is_empty());
     }
 }
@@ -0,0 +1,367 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::models::{FeedMessage, Fill};
+
+const EPSILON: f64 = 1e-9;
+
+/// Order in which lots are closed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CostBasis {
+    /// Closes the oldest lot first
+    Fifo,
+    /// Closes the newest lot first
+    Lifo,
+    /// Keeps a single lot at the average entry price
+    Average,
+}
+
+/// Open part of a fill
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lot {
+    pub trade_id: u64,
+    pub time: DateTime<Utc>,
+    pub price: f64,
+    /// Remaining size, always positive
+    pub size: f64,
+}
+
+/// Position and PnL of a single product, in the quote currency of the product
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Position {
+    pub product_id: String,
+    /// Signed size, negative when short
+    pub size: f64,
+    /// Realized PnL net of all fees paid
+    pub realized: f64,
+    /// Fees paid on all fills
+    pub fees: f64,
+    /// Traded base volume
+    pub volume: f64,
+    lots: VecDeque<Lot>,
+}
+
+impl Position {
+    fn new(product_id: &str) -> Self {
+        Self {
+            product_id: product_id.to_string(),
+            size: 0.0,
+            realized: 0.0,
+            fees: 0.0,
+            volume: 0.0,
+            lots: VecDeque::new(),
+        }
+    }
+
+    /// Returns the open lots, oldest first.
+    pub fn lots(&self) -> &VecDeque<Lot> {
+        &self.lots
+    }
+
+    /// Returns the average entry price of the open lots.
+    pub fn average_price(&self) -> Option<f64> {
+        let size: f64 = self.lots.iter().map(|lot| lot.size).sum();
+        if size == 0.0 {
+            return None;
+        }
+        Some(self.lots.iter().map(|lot| lot.price * lot.size).sum::<f64>() / size)
+    }
+
+    /// Returns the PnL of the open lots against the given mark price.
+    pub fn unrealized(&self, mark: f64) -> f64 {
+        let direction = self.size.signum();
+        self.lots.iter().map(|lot| (mark - lot.price) * lot.size * direction).sum()
+    }
+
+    fn apply(&mut self, basis: CostBasis, lot: Lot, buy: bool, fee: f64) {
+        let direction = if buy { 1.0 } else { -1.0 };
+        self.fees += fee;
+        self.realized -= fee;
+        self.volume += lot.size;
+
+        let mut remaining = lot.size;
+        // closes lots while the fill goes against the position
+        while remaining > EPSILON && self.size * direction < 0.0 {
+            let open = match basis {
+                CostBasis::Lifo => self.lots.back_mut(),
+                CostBasis::Fifo | CostBasis::Average => self.lots.front_mut(),
+            };
+            let open = match open {
+                Some(open) => open,
+                None => break,
+            };
+
+            let closed = remaining.min(open.size);
+            self.realized += (lot.price - open.price) * closed * -direction;
+            open.size -= closed;
+            remaining -= closed;
+            self.size += closed * direction;
+
+            if open.size <= EPSILON {
+                match basis {
+                    CostBasis::Lifo => self.lots.pop_back(),
+                    CostBasis::Fifo | CostBasis::Average => self.lots.pop_front(),
+                };
+            }
+        }
+
+        if self.lots.is_empty() {
+            self.size = 0.0;
+        }
+        if remaining <= EPSILON {
+            return;
+        }
+
+        self.size += remaining * direction;
+        match (basis, self.lots.front_mut()) {
+            (CostBasis::Average, Some(open)) => {
+                open.price = (open.price * open.size + lot.price * remaining) / (open.size + remaining);
+                open.size += remaining;
+                open.trade_id = lot.trade_id;
+                open.time = lot.time;
+            }
+            _ => self.lots.push_back(Lot { size: remaining, ..lot }),
+        }
+    }
+}
+
+/// Tracks positions and PnL from fills.
+///
+/// Fills must be applied oldest first, pages from `get_fills` are newest first and need to be reversed.
+/// Fills are deduplicated by trade id and side, so REST history and live events can overlap.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::client::{AuthenticatedClient, FILL, SANDBOX_URL};
+/// use cbpro::models::Fill;
+/// use cbpro::pnl::{CostBasis, Ledger};
+/// use cbpro::websocket::{Channels, WebSocketFeed, SANDBOX_FEED_URL};
+/// use futures::TryStreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+/// let mut fills: Vec<Fill> = client
+///     .get_fills(FILL::ProductID("BTC-USD"))
+///     .paginate_items::<Fill>()?
+///     .try_collect()
+///     .await?;
+/// fills.reverse();
+///
+/// let mut ledger = Ledger::new(CostBasis::Fifo).maker_fee_rate(0.005);
+/// ledger.extend(&fills);
+///
+/// let mut feed = WebSocketFeed::connect_auth("<key>", "<pass>", "<secret>", SANDBOX_FEED_URL).await?;
+/// feed.subscribe(&["BTC-USD"], &[Channels::USER, Channels::TICKER]).await?;
+///
+/// while let Some(value) = feed.json::<serde_json::Value>().await? {
+///     ledger.update(&value)?;
+///     if let (Some(position), Some(price)) = (ledger.position("BTC-USD"), value["price"].as_str()) {
+///         let mark: f64 = price.parse()?;
+///         println!("{} realized: {} unrealized: {}", position.size, position.realized, position.unrealized(mark));
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Ledger {
+    basis: CostBasis,
+    maker_fee_rate: f64,
+    taker_fee_rate: f64,
+    positions: BTreeMap<String, Position>,
+    orders: HashSet<String>,
+    seen: HashSet<(String, u64, bool)>,
+}
+
+impl Ledger {
+    /// Creates an empty ledger.
+    pub fn new(basis: CostBasis) -> Self {
+        Self {
+            basis,
+            maker_fee_rate: 0.0,
+            taker_fee_rate: 0.0,
+            positions: BTreeMap::new(),
+            orders: HashSet::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Sets the fee rate charged on live maker fills, websocket messages carry no maker fee.
+    pub fn maker_fee_rate(mut self, rate: f64) -> Self {
+        self.maker_fee_rate = rate;
+        self
+    }
+
+    /// Sets the fee rate charged on live taker fills without a `taker_fee_rate` field.
+    pub fn taker_fee_rate(mut self, rate: f64) -> Self {
+        self.taker_fee_rate = rate;
+        self
+    }
+
+    /// Tracks fills of an order through the public `matches` channel.
+    pub fn track_order<O: Into<String>>(&mut self, order_id: O) {
+        self.orders.insert(order_id.into());
+    }
+
+    /// Applies a fill and returns false if it was applied before.
+    /// # Example
+    ///
+    /// ```
+    /// use cbpro::models::Fill;
+    /// use cbpro::pnl::{CostBasis, Ledger};
+    ///
+    /// let fill = |trade_id: u64, side: &str, price: &str, size: &str| -> Fill {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "trade_id": trade_id, "product_id": "BTC-USD", "created_at": "2020-01-01T00:00:00Z",
+    ///         "price": price, "size": size, "fee": "1.0", "side": side,
+    ///     })).unwrap()
+    /// };
+    ///
+    /// let mut ledger = Ledger::new(CostBasis::Fifo);
+    /// ledger.apply(&fill(1, "buy", "100.0", "1.0"));
+    /// ledger.apply(&fill(2, "buy", "200.0", "1.0"));
+    /// ledger.apply(&fill(3, "sell", "300.0", "1.0"));
+    /// assert!(!ledger.apply(&fill(3, "sell", "300.0", "1.0")));
+    ///
+    /// let position = ledger.position("BTC-USD").unwrap();
+    /// assert_eq!(position.size, 1.0);
+    /// assert_eq!(position.realized, 200.0 - 3.0);
+    /// assert_eq!(position.average_price(), Some(200.0));
+    /// assert_eq!(position.unrealized(250.0), 50.0);
+    /// ```
+    pub fn apply(&mut self, fill: &Fill) -> bool {
+        let buy = fill.side == "buy";
+        if !self.seen.insert((fill.product_id.clone(), fill.trade_id, buy)) {
+            return false;
+        }
+
+        let lot = Lot {
+            trade_id: fill.trade_id,
+            time: fill.created_at,
+            price: fill.price,
+            size: fill.size,
+        };
+        let basis = self.basis;
+        self.positions
+            .entry(fill.product_id.clone())
+            .or_insert_with(|| Position::new(&fill.product_id))
+            .apply(basis, lot, buy, fill.fee);
+        true
+    }
+
+    /// Applies fills in the given order.
+    pub fn extend<'a, I: IntoIterator<Item = &'a Fill>>(&mut self, fills: I) {
+        for fill in fills {
+            self.apply(fill);
+        }
+    }
+
+    /// Applies `match` messages of the `user` channel or of tracked orders and returns whether a fill was applied.
+    pub fn update(&mut self, message: &serde_json::Value) -> crate::error::Result<bool> {
+        let message: FeedMessage = serde_json::from_value(message.clone())?;
+        if message.type_ != "match" {
+            return Ok(false);
+        }
+
+        let tracked = |id: &Option<String>| matches!(id, Some(id) if self.orders.contains(id));
+        let (order_id, side, taker) = if message.is_own() {
+            let order_id = if message.is_taker() { &message.taker_order_id } else { &message.maker_order_id };
+            (order_id, message.own_side(), message.is_taker())
+        } else if tracked(&message.maker_order_id) {
+            (&message.maker_order_id, message.own_side(), false)
+        } else if tracked(&message.taker_order_id) {
+            let side = if message.own_side() == "buy" { "sell" } else { "buy" };
+            (&message.taker_order_id, side, true)
+        } else {
+            return Ok(false);
+        };
+
+        let (product_id, trade_id, price, size, time) = match message {
+            FeedMessage {
+                product_id: Some(ref product_id),
+                trade_id: Some(trade_id),
+                price: Some(price),
+                size: Some(size),
+                time: Some(time),
+                ..
+            } => (product_id.clone(), trade_id, price, size, time),
+            _ => return Ok(false),
+        };
+        let rate = if taker {
+            message.taker_fee_rate.unwrap_or(self.taker_fee_rate)
+        } else {
+            self.maker_fee_rate
+        };
+
+        let fill = Fill {
+            trade_id,
+            product_id,
+            order_id: order_id.clone().unwrap_or_default(),
+            created_at: time,
+            price,
+            size,
+            fee: price * size * rate,
+            side: side.to_string(),
+            liquidity: if taker { "T" } else { "M" }.to_string(),
+            settled: false,
+        };
+        Ok(self.apply(&fill))
+    }
+
+    /// Returns the position of a product.
+    pub fn position(&self, product_id: &str) -> Option<&Position> {
+        self.positions.get(product_id)
+    }
+
+    /// Returns all positions ordered by product id.
+    pub fn positions(&self) -> impl Iterator<Item = &Position> {
+        self.positions.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(trade_id: u64, side: &str, price: f64, size: f64) -> Fill {
+        Fill {
+            trade_id,
+            product_id: "BTC-USD".to_string(),
+            order_id: String::new(),
+            created_at: Utc::now(),
+            price,
+            size,
+            fee: 0.0,
+            side: side.to_string(),
+            liquidity: String::new(),
+            settled: true,
+        }
+    }
+
+    #[test]
+    fn rounding_residue_closes_lot() {
+        for &basis in &[CostBasis::Fifo, CostBasis::Lifo, CostBasis::Average] {
+            let mut ledger = Ledger::new(basis);
+            ledger.apply(&fill(1, "buy", 100.0, 0.1));
+            ledger.apply(&fill(2, "buy", 100.0, 0.2));
+            ledger.apply(&fill(3, "sell", 110.0, 0.3));
+
+            let position = ledger.position("BTC-USD").unwrap();
+            assert!(position.lots().is_empty(), "{:?}", basis);
+            assert_eq!(position.size, 0.0);
+            assert!((position.realized - 3.0).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn rounding_residue_does_not_flip_position() {
+        let mut ledger = Ledger::new(CostBasis::Fifo);
+        ledger.apply(&fill(1, "sell", 100.0, 0.3));
+        ledger.apply(&fill(2, "buy", 90.0, 0.1));
+        ledger.apply(&fill(3, "buy", 90.0, 0.2));
+
+        let position = ledger.position("BTC-USD").unwrap();
+        assert!(position.lots().is_empty());
+        assert_eq!(position.size, 0.0);
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use serde::Serialize;
+use crate::client::AuthenticatedClient;
+use crate::models::{Account, FeedMessage, Product, Ticker};
+
+/// Currencies tried as intermediate step when no product links an asset to the quote currency
+const HUBS: &[&str] = &["USD", "BTC"];
+
+#[derive(Debug, Clone)]
+struct Leg {
+    product_id: String,
+    /// Set when the leg sells the quote currency of the product
+    inverse: bool,
+}
+
+/// Balances and valuation of a single currency
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Holding {
+    pub currency: String,
+    pub balance: f64,
+    pub available: f64,
+    pub hold: f64,
+    /// Price of one unit in the quote currency, `None` without a conversion path or price
+    pub price: Option<f64>,
+    /// Balance valued in the quote currency
+    pub value: Option<f64>,
+}
+
+/// Account balances valued in a single quote currency.
+///
+/// Assets are converted through the product trading them against the quote currency,
+/// or through two products via USD or BTC when no such product exists.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+/// use cbpro::portfolio::Portfolio;
+/// use cbpro::websocket::{Channels, WebSocketFeed, SANDBOX_FEED_URL};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+/// let mut portfolio = Portfolio::load(&client, "USD").await?;
+///
+/// for holding in portfolio.holdings() {
+///     println!("{} {} = {:?} USD", holding.balance, holding.currency, holding.value);
+/// }
+///
+/// let mut feed = WebSocketFeed::connect_auth("<key>", "<pass>", "<secret>", SANDBOX_FEED_URL).await?;
+/// feed.subscribe(&portfolio.product_ids(), &[Channels::TICKER, Channels::USER]).await?;
+///
+/// while let Some(value) = feed.json::<serde_json::Value>().await? {
+///     if portfolio.update(&value)? {
+///         println!("total: {} USD", portfolio.total());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Portfolio {
+    quote: String,
+    products: HashMap<String, Product>,
+    accounts: BTreeMap<String, Account>,
+    routes: HashMap<String, Vec<Leg>>,
+    prices: HashMap<String, f64>,
+    /// Orders on the book whose holds are tracked
+    open: HashSet<String>,
+}
+
+impl Portfolio {
+    /// Fetches products, accounts and the tickers needed to value every non-zero balance.
+    pub async fn load(client: &AuthenticatedClient, quote: &str) -> crate::error::Result<Portfolio> {
+        let products: Vec<Product> = client.public().get_products().json().await?;
+        let mut portfolio = Portfolio {
+            quote: quote.to_string(),
+            products: products.into_iter().map(|product| (product.id.clone(), product)).collect(),
+            accounts: BTreeMap::new(),
+            routes: HashMap::new(),
+            prices: HashMap::new(),
+            open: HashSet::new(),
+        };
+        portfolio.refresh(client).await?;
+        Ok(portfolio)
+    }
+
+    /// Refetches accounts and tickers, e.g. to account for fees not included in live updates.
+    pub async fn refresh(&mut self, client: &AuthenticatedClient) -> crate::error::Result<()> {
+        let accounts: Vec<Account> = client.list_accounts().json().await?;
+        self.accounts = accounts.into_iter().map(|account| (account.currency.clone(), account)).collect();
+        self.open.clear();
+
+        let currencies: Vec<String> = self.accounts
+            .values()
+            .filter(|account| account.balance != 0.0 || account.hold != 0.0)
+            .map(|account| account.currency.clone())
+            .collect();
+        for currency in currencies {
+            self.resolve(&currency);
+        }
+
+        let product_ids: Vec<String> = self.product_ids().into_iter().map(String::from).collect();
+        for product_id in product_ids {
+            let ticker: Ticker = client.public().get_product_ticker(&product_id).json().await?;
+            self.prices.insert(product_id, ticker.price);
+        }
+        Ok(())
+    }
+
+    /// Returns the currency holdings are valued in.
+    pub fn quote(&self) -> &str {
+        &self.quote
+    }
+
+    /// Returns the products whose prices value the holdings, subscribe to their `ticker` channel to keep prices live.
+    pub fn product_ids(&self) -> Vec<&str> {
+        let ids: BTreeSet<&str> = self.routes
+            .values()
+            .flat_map(|legs| legs.iter().map(|leg| leg.product_id.as_str()))
+            .collect();
+        ids.into_iter().collect()
+    }
+
+    /// Returns the price of one unit of currency in the quote currency.
+    pub fn price(&self, currency: &str) -> Option<f64> {
+        let legs = self.routes.get(currency)?;
+        let mut price = 1.0;
+        for leg in legs {
+            let rate = *self.prices.get(&leg.product_id)?;
+            price *= if leg.inverse { 1.0 / rate } else { rate };
+        }
+        Some(price)
+    }
+
+    /// Returns balances and valuation of a single currency.
+    pub fn holding(&self, currency: &str) -> Option<Holding> {
+        let account = self.accounts.get(currency)?;
+        let price = self.price(currency);
+        Some(Holding {
+            currency: account.currency.clone(),
+            balance: account.balance,
+            available: account.available,
+            hold: account.hold,
+            price,
+            value: price.map(|price| price * account.balance),
+        })
+    }
+
+    /// Returns every non-zero balance ordered by currency.
+    pub fn holdings(&self) -> Vec<Holding> {
+        self.accounts
+            .values()
+            .filter(|account| account.balance != 0.0 || account.hold != 0.0)
+            .filter_map(|account| self.holding(&account.currency))
+            .collect()
+    }
+
+    /// Returns the summed value of all holdings that could be valued.
+    pub fn total(&self) -> f64 {
+        self.holdings().iter().filter_map(|holding| holding.value).sum()
+    }
+
+    /// Sets the last price of a product.
+    pub fn set_price(&mut self, product_id: &str, price: f64) {
+        self.prices.insert(product_id.to_string(), price);
+    }
+
+    /// Applies a websocket message and returns whether prices or balances changed.
+    ///
+    /// `ticker` messages update prices. Messages of the `user` channel update balances on `match`
+    /// and the holds of orders resting on the book on `open`, `match` and `done`.
+    /// Fees and holds of orders placed before the last refresh are not part of these messages
+    /// and are only picked up by [refresh](#method.refresh).
+    pub fn update(&mut self, message: &serde_json::Value) -> crate::error::Result<bool> {
+        let message: FeedMessage = serde_json::from_value(message.clone())?;
+        let product = match message.product_id.as_ref().and_then(|id| self.products.get(id)) {
+            Some(product) => product.clone(),
+            None => return Ok(false),
+        };
+        let own = message.is_own();
+
+        match (message.type_.as_str(), message.price) {
+            ("ticker", Some(price)) => {
+                self.prices.insert(product.id, price);
+                Ok(true)
+            }
+            ("match", Some(price)) if own => {
+                let size = message.size.unwrap_or(0.0);
+                let taker = message.is_taker();
+                let buy = message.own_side() == "buy";
+                let (base, quote) = if buy { (size, -size * price) } else { (-size, size * price) };
+                self.credit(&product.base_currency, base);
+                self.credit(&product.quote_currency, quote);
+                if let Some(ref order_id) = message.maker_order_id {
+                    if !taker && self.open.contains(order_id) {
+                        self.release(&product, buy, price, size);
+                    }
+                }
+                Ok(true)
+            }
+            ("open", Some(price)) if own => {
+                let buy = message.side.as_deref() == Some("buy");
+                let size = message.remaining_size.unwrap_or(0.0);
+                if let Some(order_id) = message.order_id {
+                    self.open.insert(order_id);
+                }
+                self.release(&product, buy, price, -size);
+                Ok(true)
+            }
+            ("done", Some(price)) if own => {
+                let opened = match message.order_id {
+                    Some(ref order_id) => self.open.remove(order_id),
+                    None => false,
+                };
+                let buy = message.side.as_deref() == Some("buy");
+                let size = message.remaining_size.unwrap_or(0.0);
+                if opened && size != 0.0 {
+                    self.release(&product, buy, price, size);
+                    return Ok(true);
+                }
+                Ok(false)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn account(&mut self, currency: &str) -> &mut Account {
+        if !self.accounts.contains_key(currency) {
+            self.accounts.insert(currency.to_string(), Account {
+                id: String::new(),
+                currency: currency.to_string(),
+                balance: 0.0,
+                available: 0.0,
+                hold: 0.0,
+                profile_id: String::new(),
+                trading_enabled: true,
+            });
+        }
+        self.resolve(currency);
+        self.accounts.get_mut(currency).unwrap()
+    }
+
+    fn credit(&mut self, currency: &str, amount: f64) {
+        let account = self.account(currency);
+        account.balance += amount;
+        account.available = account.balance - account.hold;
+    }
+
+    /// Releases the hold of `size` units of an order on the book, a negative size places a hold.
+    fn release(&mut self, product: &Product, buy: bool, price: f64, size: f64) {
+        let (currency, amount) = if buy {
+            (&product.quote_currency, size * price)
+        } else {
+            (&product.base_currency, size)
+        };
+        let account = self.account(currency);
+        account.hold = (account.hold - amount).max(0.0);
+        account.available = account.balance - account.hold;
+    }
+
+    fn resolve(&mut self, currency: &str) {
+        if self.routes.contains_key(currency) {
+            return;
+        }
+        if let Some(legs) = route(&self.products, currency, &self.quote) {
+            self.routes.insert(currency.to_string(), legs);
+        }
+    }
+}
+
+fn direct(products: &HashMap<String, Product>, from: &str, to: &str) -> Option<Leg> {
+    products
+        .values()
+        .filter(|product| product.status != "delisted")
+        .find_map(|product| {
+            if product.base_currency == from && product.quote_currency == to {
+                Some(Leg { product_id: product.id.clone(), inverse: false })
+            } else if product.base_currency == to && product.quote_currency == from {
+                Some(Leg { product_id: product.id.clone(), inverse: true })
+            } else {
+                None
+            }
+        })
+}
+
+fn route(products: &HashMap<String, Product>, from: &str, to: &str) -> Option<Vec<Leg>> {
+    if from == to {
+        return Some(Vec::new());
+    }
+    if let Some(leg) = direct(products, from, to) {
+        return Some(vec![leg]);
+    }
+    HUBS.iter()
+        .filter(|&&hub| hub != from && hub != to)
+        .find_map(|hub| Some(vec![direct(products, from, hub)?, direct(products, hub, to)?]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn product(base: &str, quote: &str) -> Product {
+        serde_json::from_value(json!({
+            "id": format!("{}-{}", base, quote),
+            "base_currency": base,
+            "quote_currency": quote,
+            "base_increment": "0.00000001",
+            "quote_increment": "0.01",
+            "status": "online",
+        }))
+        .unwrap()
+    }
+
+    fn account(currency: &str, balance: f64, hold: f64) -> Account {
+        Account {
+            id: format!("{}-account", currency),
+            currency: currency.to_string(),
+            balance,
+            available: balance - hold,
+            hold,
+            profile_id: String::new(),
+            trading_enabled: true,
+        }
+    }
+
+    fn portfolio(accounts: Vec<Account>) -> Portfolio {
+        let products = vec![product("BTC", "USD"), product("ETH", "BTC"), product("USDC", "EUR"), product("EUR", "USD")];
+        let mut portfolio = Portfolio {
+            quote: "USD".to_string(),
+            products: products.into_iter().map(|product| (product.id.clone(), product)).collect(),
+            accounts: accounts.into_iter().map(|account| (account.currency.clone(), account)).collect(),
+            routes: HashMap::new(),
+            prices: HashMap::new(),
+            open: HashSet::new(),
+        };
+        let currencies: Vec<String> = portfolio.accounts.keys().cloned().collect();
+        for currency in currencies {
+            portfolio.resolve(&currency);
+        }
+        portfolio
+    }
+
+    fn own(message: serde_json::Value) -> serde_json::Value {
+        let mut message = message;
+        message["user_id"] = json!("user");
+        message["profile_id"] = json!("profile");
+        message
+    }
+
+    #[test]
+    fn routes_go_direct_inverse_or_through_hub() {
+        let portfolio = portfolio(vec![account("USD", 1.0, 0.0)]);
+        let products = &portfolio.products;
+
+        let legs = route(products, "BTC", "USD").unwrap();
+        assert_eq!((legs[0].product_id.as_str(), legs[0].inverse), ("BTC-USD", false));
+
+        let legs = route(products, "USD", "EUR").unwrap();
+        assert_eq!((legs[0].product_id.as_str(), legs[0].inverse), ("EUR-USD", true));
+
+        let legs = route(products, "ETH", "USD").unwrap();
+        let ids: Vec<&str> = legs.iter().map(|leg| leg.product_id.as_str()).collect();
+        assert_eq!(ids, vec!["ETH-BTC", "BTC-USD"]);
+
+        assert!(route(products, "USD", "USD").unwrap().is_empty());
+        assert!(route(products, "XRP", "USD").is_none());
+    }
+
+    #[test]
+    fn holdings_are_valued_through_routes() {
+        let mut portfolio = portfolio(vec![
+            account("USD", 100.0, 0.0),
+            account("BTC", 2.0, 0.5),
+            account("ETH", 10.0, 0.0),
+            account("USDC", 50.0, 0.0),
+            account("XRP", 5.0, 0.0),
+            account("LTC", 0.0, 0.0),
+        ]);
+        portfolio.set_price("BTC-USD", 10000.0);
+        portfolio.set_price("ETH-BTC", 0.02);
+        portfolio.set_price("EUR-USD", 1.25);
+
+        assert_eq!(portfolio.product_ids(), vec!["BTC-USD", "ETH-BTC"]);
+        assert_eq!(portfolio.price("USD"), Some(1.0));
+        assert_eq!(portfolio.price("ETH"), Some(200.0));
+        // no USDC-USD and USDC-EUR is not a route through a hub
+        assert_eq!(portfolio.price("USDC"), None);
+
+        let btc = portfolio.holding("BTC").unwrap();
+        assert_eq!((btc.available, btc.value), (1.5, Some(20000.0)));
+        assert_eq!(portfolio.holding("XRP").unwrap().value, None);
+
+        let currencies: Vec<String> = portfolio.holdings().into_iter().map(|holding| holding.currency).collect();
+        assert_eq!(currencies, vec!["BTC", "ETH", "USD", "USDC", "XRP"]);
+        assert_eq!(portfolio.total(), 100.0 + 20000.0 + 2000.0);
+    }
+
+    #[test]
+    fn ticker_updates_price() {
+        let mut portfolio = portfolio(vec![account("BTC", 1.0, 0.0)]);
+
+        let ticker = json!({"type": "ticker", "product_id": "BTC-USD", "price": "9000.00"});
+        assert!(portfolio.update(&ticker).unwrap());
+        assert_eq!(portfolio.total(), 9000.0);
+
+        let unknown = json!({"type": "ticker", "product_id": "XRP-USD", "price": "0.20"});
+        assert!(!portfolio.update(&unknown).unwrap());
+    }
+
+    #[test]
+    fn own_matches_move_balances() {
+        let mut portfolio = portfolio(vec![account("USD", 1000.0, 0.0)]);
+
+        // maker side of the match is sell, the own order took it as a buy
+        let taker_buy = own(json!({
+            "type": "match", "product_id": "BTC-USD", "side": "sell", "price": "100.00", "size": "2.0",
+            "maker_order_id": "maker", "taker_order_id": "taker", "taker_user_id": "user",
+        }));
+        assert!(portfolio.update(&taker_buy).unwrap());
+        assert_eq!(portfolio.holding("USD").unwrap().balance, 800.0);
+        assert_eq!(portfolio.holding("BTC").unwrap().balance, 2.0);
+
+        let maker_buy = own(json!({
+            "type": "match", "product_id": "BTC-USD", "side": "buy", "price": "100.00", "size": "1.0",
+            "maker_order_id": "maker", "taker_order_id": "taker",
+        }));
+        assert!(portfolio.update(&maker_buy).unwrap());
+        assert_eq!(portfolio.holding("USD").unwrap().balance, 700.0);
+        assert_eq!(portfolio.holding("BTC").unwrap().balance, 3.0);
+
+        let public = json!({"type": "match", "product_id": "BTC-USD", "side": "buy", "price": "100.00", "size": "1.0"});
+        assert!(!portfolio.update(&public).unwrap());
+        assert_eq!(portfolio.holding("BTC").unwrap().balance, 3.0);
+    }
+
+    #[test]
+    fn holds_follow_resting_orders() {
+        let mut portfolio = portfolio(vec![account("USD", 1000.0, 0.0), account("BTC", 1.0, 0.0)]);
+
+        let open = own(json!({
+            "type": "open", "product_id": "BTC-USD", "order_id": "bid", "side": "buy", "price": "100.00", "remaining_size": "3.0",
+        }));
+        assert!(portfolio.update(&open).unwrap());
+        assert_eq!(portfolio.holding("USD").unwrap().hold, 300.0);
+        assert_eq!(portfolio.holding("USD").unwrap().available, 700.0);
+
+        let fill = own(json!({
+            "type": "match", "product_id": "BTC-USD", "side": "buy", "price": "100.00", "size": "1.0",
+            "maker_order_id": "bid", "taker_order_id": "other",
+        }));
+        portfolio.update(&fill).unwrap();
+        let usd = portfolio.holding("USD").unwrap();
+        assert_eq!((usd.balance, usd.hold, usd.available), (900.0, 200.0, 700.0));
+
+        let done = own(json!({
+            "type": "done", "product_id": "BTC-USD", "order_id": "bid", "side": "buy", "price": "100.00",
+            "remaining_size": "2.0", "reason": "canceled",
+        }));
+        assert!(portfolio.update(&done).unwrap());
+        let usd = portfolio.holding("USD").unwrap();
+        assert_eq!((usd.hold, usd.available), (0.0, 900.0));
+
+        // holds of orders placed before the last refresh are unknown and left alone
+        let unknown = own(json!({
+            "type": "done", "product_id": "BTC-USD", "order_id": "old", "side": "sell", "price": "100.00", "remaining_size": "1.0",
+        }));
+        assert!(!portfolio.update(&unknown).unwrap());
+    }
+}
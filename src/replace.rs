@@ -0,0 +1,146 @@
+use core::time::Duration;
+use std::time::Instant;
+use uuid::Uuid;
+use crate::batch::{BatchItem, OrderKind, OrderRequest};
+use crate::client::{AuthenticatedClient, ORD};
+use crate::error::Error;
+use crate::models::Order;
+
+/// Path taken by a [ReplaceOrder](struct.ReplaceOrder.html)
+#[derive(Debug)]
+pub enum ReplaceOutcome {
+    /// The old order was canceled without fills and the new order placed.
+    /// `canceled` is `None` when the record of the canceled order was already purged.
+    Replaced { canceled: Option<Order>, placed: Order },
+    /// The old order was canceled after a partial fill and the new order placed
+    PartiallyFilled { canceled: Order, placed: Order },
+    /// The old order filled before it could be canceled, nothing was placed
+    Filled { order: Order },
+    /// The old order does not exist, nothing was placed
+    NotFound,
+    /// The cancel was not confirmed within the timeout, nothing was placed
+    Unconfirmed { order: Option<Order> },
+    /// The old order was canceled but placing the new order failed
+    PlaceFailed { canceled: Option<Order>, error: Error },
+}
+
+impl ReplaceOutcome {
+    /// Returns the new order if one was placed.
+    pub fn placed(&self) -> Option<&Order> {
+        match self {
+            ReplaceOutcome::Replaced { placed, .. } | ReplaceOutcome::PartiallyFilled { placed, .. } => Some(placed),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the client oid linked to an order id, the same order id always yields the same client oid.
+pub fn linked_client_oid(order_id: &str) -> String {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, format!("cbpro:replace:{}", order_id).as_bytes()).to_string()
+}
+
+/// Cancel and replace returned by [replace_order](../client/struct.AuthenticatedClient.html#method.replace_order).
+///
+/// The new order is only placed once `get_order` confirms the old order is done or purged,
+/// so both orders are never open at the same time.
+pub struct ReplaceOrder<'a> {
+    client: AuthenticatedClient,
+    ord: ORD<'a>,
+    request: OrderRequest,
+    confirm_timeout: Duration,
+    reduce_by_filled: bool,
+}
+
+impl<'a> ReplaceOrder<'a> {
+    pub(super) fn new(client: AuthenticatedClient, ord: ORD<'a>, request: OrderRequest) -> Self {
+        Self {
+            client,
+            ord,
+            request,
+            confirm_timeout: Duration::from_secs(10),
+            reduce_by_filled: false,
+        }
+    }
+
+    /// Sets how long to wait for the cancel to be confirmed, defaults to 10 seconds.
+    pub fn confirm_timeout(mut self, value: Duration) -> Self {
+        self.confirm_timeout = value;
+        self
+    }
+
+    /// Reduces the size of a new limit order by the size the old order filled.
+    pub fn reduce_by_filled(mut self, value: bool) -> Self {
+        self.reduce_by_filled = value;
+        self
+    }
+
+    /// Cancels the old order, waits for the confirmation and places the new order.
+    ///
+    /// Without a client oid on the request the new order gets one [linked](fn.linked_client_oid.html) to the old order id.
+    pub async fn send(self) -> crate::error::Result<ReplaceOutcome> {
+        let ReplaceOrder { client, ord, mut request, confirm_timeout, reduce_by_filled } = self;
+
+        // a done order answers with 400 or 404, get_order tells which way it went
+        let canceled = match client.cancel_order(ord).text().await {
+            Ok(_) => true,
+            Err(ref error) if matches!(error.status(), Some(400) | Some(404)) => false,
+            Err(error) => return Err(error),
+        };
+
+        let started = Instant::now();
+        let mut delay = Duration::from_millis(100);
+        let order = loop {
+            let last = match client.get_order(ord).json::<Order>().await {
+                Ok(order) if order.status == "done" => break Some(order),
+                Ok(order) => Some(order),
+                // orders canceled without fills are purged
+                Err(ref error) if error.status() == Some(404) && canceled => break None,
+                Err(ref error) if error.status() == Some(404) => return Ok(ReplaceOutcome::NotFound),
+                Err(error) => return Err(error),
+            };
+            if started.elapsed() >= confirm_timeout {
+                return Ok(ReplaceOutcome::Unconfirmed { order: last });
+            }
+            tokio::time::delay_for(delay).await;
+            delay = (delay * 2).min(Duration::from_secs(1));
+        };
+
+        let filled = order.as_ref().map_or(0.0, |order| order.filled_size);
+        if let Some(order) = order {
+            if order.done_reason.as_deref() == Some("filled") {
+                return Ok(ReplaceOutcome::Filled { order });
+            }
+            if reduce_by_filled && filled > 0.0 {
+                if let OrderKind::Limit { price, size } = request.kind {
+                    if size - filled <= 0.0 {
+                        return Ok(ReplaceOutcome::Filled { order });
+                    }
+                    request.kind = OrderKind::Limit { price, size: size - filled };
+                }
+            }
+            if request.client_oid.is_none() {
+                request.client_oid = Some(linked_client_oid(&order.id));
+            }
+            return Ok(match place(&client, &request).await {
+                Ok(placed) if filled > 0.0 => ReplaceOutcome::PartiallyFilled { canceled: order, placed },
+                Ok(placed) => ReplaceOutcome::Replaced { canceled: Some(order), placed },
+                Err(error) => ReplaceOutcome::PlaceFailed { canceled: Some(order), error },
+            });
+        }
+
+        if request.client_oid.is_none() {
+            if let ORD::OrderID(id) = ord {
+                request.client_oid = Some(linked_client_oid(id));
+            }
+        }
+        Ok(match place(&client, &request).await {
+            Ok(placed) => ReplaceOutcome::Replaced { canceled: None, placed },
+            Err(error) => ReplaceOutcome::PlaceFailed { canceled: None, error },
+        })
+    }
+}
+
+async fn place(client: &AuthenticatedClient, request: &OrderRequest) -> crate::error::Result<Order> {
+    let text = request.send(client).await?;
+    Ok(serde_json::from_str(&text)?)
+}
@@ -0,0 +1,285 @@
+use core::time::Duration;
+use std::path::Path;
+use std::time::Instant;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt};
+use reqwest::{Client, Response};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use crate::builder::{QueryBuilder, ReportOptions};
+use crate::client::AuthenticatedClient;
+use crate::error::{CBError, Error, Kind};
+use crate::models::Report;
+
+/// Stream of report file chunks
+pub type ReportStream = BoxStream<'static, crate::error::Result<Vec<u8>>>;
+
+/// Row of a `fills` report in csv format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FillRow {
+    /// Only present on reports generated after the introduction of portfolios
+    #[serde(default)]
+    pub portfolio: Option<String>,
+    #[serde(rename = "trade id")]
+    pub trade_id: u64,
+    pub product: String,
+    pub side: String,
+    #[serde(rename = "created at")]
+    pub created_at: DateTime<Utc>,
+    pub size: f64,
+    #[serde(rename = "size unit")]
+    pub size_unit: String,
+    pub price: f64,
+    pub fee: f64,
+    /// Signed quote amount including the fee
+    pub total: f64,
+    #[serde(rename = "price/fee/total unit")]
+    pub unit: String,
+}
+
+/// Row of an `account` report in csv format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountRow {
+    /// Only present on reports generated after the introduction of portfolios
+    #[serde(default)]
+    pub portfolio: Option<String>,
+    /// One of `match`, `fee`, `deposit`, `withdrawal` or `conversion`
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub time: DateTime<Utc>,
+    pub amount: f64,
+    pub balance: f64,
+    #[serde(rename = "amount/balance unit")]
+    pub unit: String,
+    #[serde(rename = "transfer id")]
+    pub transfer_id: Option<String>,
+    #[serde(rename = "trade id")]
+    pub trade_id: Option<u64>,
+    #[serde(rename = "order id")]
+    pub order_id: Option<String>,
+}
+
+/// Parses rows of a csv report.
+/// # Example
+///
+/// ```
+/// use cbpro::reports::{parse_csv, FillRow};
+///
+/// let csv = "\
+/// portfolio,trade id,product,side,created at,size,size unit,price,fee,total,price/fee/total unit
+/// default,1,BTC-USD,BUY,2020-01-02T03:04:05.678Z,0.5,BTC,7000.00,17.5,-3517.5,USD
+/// ";
+/// let fills: Vec<FillRow> = parse_csv(csv.as_bytes())?;
+/// assert_eq!(fills[0].trade_id, 1);
+/// assert_eq!(fills[0].total, -3517.5);
+/// # Ok::<(), cbpro::error::Error>(())
+/// ```
+pub fn parse_csv<R: std::io::Read, T: DeserializeOwned>(reader: R) -> crate::error::Result<Vec<T>> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let mut rows = Vec::new();
+    for row in reader.deserialize() {
+        rows.push(row?);
+    }
+    Ok(rows)
+}
+
+async fn fetch_file(client: &Client, file_url: &str) -> crate::error::Result<Response> {
+    let resp = client.get(file_url).send().await?;
+    if resp.status().is_success() {
+        Ok(resp)
+    } else {
+        let error = CBError::new(resp.status().as_u16(), resp.text().await?);
+        Err(error.into())
+    }
+}
+
+fn file_url(report: &Report) -> crate::error::Result<&str> {
+    match report.file_url {
+        Some(ref url) => Ok(url),
+        None => Err(Error::new(Kind::Report, Some(format!("report {} is ready without a file_url", report.id)))),
+    }
+}
+
+/// Creates a report, waits until it is ready and downloads it,
+/// see [generate_report](../client/struct.AuthenticatedClient.html#method.generate_report).
+pub struct ReportRequest<'a> {
+    client: AuthenticatedClient,
+    create: QueryBuilder<ReportOptions<'a>>,
+    interval: Duration,
+    max_interval: Duration,
+    timeout: Option<Duration>,
+}
+
+impl<'a> ReportRequest<'a> {
+    pub(super) fn new(client: AuthenticatedClient, create: QueryBuilder<ReportOptions<'a>>) -> Self {
+        Self {
+            client,
+            create,
+            interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(30),
+            timeout: None,
+        }
+    }
+    /// Sets format of output report.
+    /// Valid inputs are "pdf" or "csv" (defualt is pdf)
+    pub fn format(mut self, value: &'a str) -> Self {
+        self.create = self.create.format(value);
+        self
+    }
+    /// Sets email to send report to.
+    pub fn email(mut self, value: &'a str) -> Self {
+        self.create = self.create.email(value);
+        self
+    }
+    /// Sets the first and the largest delay between status polls, the delay doubles after every poll.
+    /// Defaults to 1 and 30 seconds.
+    pub fn poll_interval(mut self, initial: Duration, max: Duration) -> Self {
+        self.interval = initial;
+        self.max_interval = max.max(initial);
+        self
+    }
+    /// Fails with a report error if the report is not ready in time.
+    pub fn timeout(mut self, value: Duration) -> Self {
+        self.timeout = Some(value);
+        self
+    }
+    /// Creates the report and polls its status until it is ready.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL, RPT};
+    /// use chrono::{ TimeZone, Utc };
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let start_date = Utc.ymd(2018, 8, 10).and_hms(0, 0, 0);
+    /// let end_date = Utc.ymd(2018, 8, 28).and_hms(0, 0, 0);
+    ///
+    /// let report = client
+    ///     .generate_report(start_date, end_date, RPT::Fills { product_id: "BTC-USD" })
+    ///     .timeout(core::time::Duration::from_secs(300))
+    ///     .ready()
+    ///     .await?;
+    ///
+    /// println!("{:?}", report.file_url);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn ready(self) -> crate::error::Result<Report> {
+        let ReportRequest { client, create, mut interval, max_interval, timeout } = self;
+        let started = Instant::now();
+        let mut report: Report = create.json().await?;
+
+        loop {
+            match report.status.as_str() {
+                "ready" => return Ok(report),
+                "pending" | "creating" => (),
+                status => {
+                    let message = format!("report {} failed with status {}", report.id, status);
+                    return Err(Error::new(Kind::Report, Some(message)));
+                }
+            }
+
+            if let Some(timeout) = timeout {
+                if started.elapsed() + interval > timeout {
+                    let message = format!("report {} not ready after {:?}", report.id, timeout);
+                    return Err(Error::new(Kind::Report, Some(message)));
+                }
+            }
+            tokio::time::delay_for(interval).await;
+            interval = (interval * 2).min(max_interval);
+
+            report = client.get_report_status(&report.id).json().await?;
+        }
+    }
+    /// Waits for the report and returns its file as a stream of chunks.
+    pub async fn stream(self) -> crate::error::Result<ReportStream> {
+        let client = self.client.client().clone();
+        let report = self.ready().await?;
+        let resp = fetch_file(&client, file_url(&report)?).await?;
+
+        let chunks = stream::try_unfold(resp, |mut resp| async move {
+            match resp.chunk().await? {
+                Some(chunk) => Ok(Some((chunk.to_vec(), resp))),
+                None => Ok(None),
+            }
+        });
+        Ok(chunks.boxed())
+    }
+    /// Waits for the report and returns its file.
+    pub async fn bytes(self) -> crate::error::Result<Vec<u8>> {
+        let client = self.client.client().clone();
+        let report = self.ready().await?;
+        let resp = fetch_file(&client, file_url(&report)?).await?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+    /// Waits for the report and writes its file to the given path.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL, RPT};
+    /// use chrono::{ TimeZone, Utc };
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let start_date = Utc.ymd(2018, 8, 10).and_hms(0, 0, 0);
+    /// let end_date = Utc.ymd(2018, 8, 28).and_hms(0, 0, 0);
+    ///
+    /// let report = client
+    ///     .generate_report(start_date, end_date, RPT::Account { account_id: "<account_id>" })
+    ///     .format("pdf")
+    ///     .download("account.pdf")
+    ///     .await?;
+    ///
+    /// println!("{:?}", report);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn download<P: AsRef<Path>>(self, path: P) -> crate::error::Result<Report> {
+        let client = self.client.client().clone();
+        let report = self.ready().await?;
+        let mut chunks = fetch_file(&client, file_url(&report)?).await?;
+
+        let mut file = tokio::fs::File::create(path).await?;
+        while let Some(chunk) = chunks.chunk().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok(report)
+    }
+    /// Generates a `fills` report in csv format and parses its rows.
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL, RPT};
+    /// use chrono::{ TimeZone, Utc };
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let start_date = Utc.ymd(2018, 8, 10).and_hms(0, 0, 0);
+    /// let end_date = Utc.ymd(2018, 8, 28).and_hms(0, 0, 0);
+    ///
+    /// let fills = client
+    ///     .generate_report(start_date, end_date, RPT::Fills { product_id: "BTC-USD" })
+    ///     .fills()
+    ///     .await?;
+    ///
+    /// for fill in fills {
+    ///     println!("{} {} {} @ {}", fill.side, fill.size, fill.product, fill.price);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fills(self) -> crate::error::Result<Vec<FillRow>> {
+        let bytes = self.format("csv").bytes().await?;
+        parse_csv(bytes.as_slice())
+    }
+    /// Generates an `account` report in csv format and parses its rows.
+    pub async fn account(self) -> crate::error::Result<Vec<AccountRow>> {
+        let bytes = self.format("csv").bytes().await?;
+        parse_csv(bytes.as_slice())
+    }
+}
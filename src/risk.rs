@@ -0,0 +1,498 @@
+use core::time::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::builder::CBParams;
+use crate::client::QTY;
+use crate::error::{Error, Kind};
+use crate::models::FeedMessage;
+
+/// Reason an order was rejected by a [RiskGate](struct.RiskGate.html), returned by
+/// [Error::risk](../error/struct.Error.html#method.risk)
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskViolation {
+    /// The kill switch is engaged
+    KillSwitch,
+    /// The quote value of the order exceeds the limit
+    MaxNotional { notional: f64, limit: f64 },
+    /// The position after a full fill of the order and of the open orders on its side would exceed the limit
+    MaxPosition { position: f64, limit: f64 },
+    /// The limit price is too far through the best bid or ask
+    PriceCollar { price: f64, reference: f64 },
+    /// More orders than allowed were placed within the last second
+    RateLimit { limit: u32 },
+    /// A check needs the best bid or ask of a product that was never set
+    NoReferencePrice { product_id: String },
+}
+
+impl fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RiskViolation::KillSwitch => write!(f, "kill switch engaged"),
+            RiskViolation::MaxNotional { notional, limit } => write!(f, "notional {} exceeds {}", notional, limit),
+            RiskViolation::MaxPosition { position, limit } => write!(f, "position {} exceeds {}", position, limit),
+            RiskViolation::PriceCollar { price, reference } => write!(f, "price {} outside of the collar around {}", price, reference),
+            RiskViolation::RateLimit { limit } => write!(f, "more than {} orders per second", limit),
+            RiskViolation::NoReferencePrice { product_id } => write!(f, "no best bid or ask for {}", product_id),
+        }
+    }
+}
+
+impl std::error::Error for RiskViolation {}
+
+// done messages kept for orders whose placement response has not been settled yet
+const RECENT_DONE: usize = 100;
+
+/// Remaining size of an order that passed the gate
+struct Exposure {
+    product_id: String,
+    buy: bool,
+    remaining: f64,
+}
+
+#[derive(Default)]
+struct State {
+    killed: bool,
+    quotes: HashMap<String, (f64, f64)>,
+    positions: HashMap<String, f64>,
+    placed: VecDeque<Instant>,
+    next_ticket: u64,
+    // orders sent but not answered yet, by ticket
+    pending: HashMap<u64, Exposure>,
+    // open orders, by order id
+    open: HashMap<String, Exposure>,
+    recent_done: VecDeque<String>,
+}
+
+impl State {
+    fn open_size(&self, product_id: &str, buy: bool) -> f64 {
+        self.pending
+            .values()
+            .chain(self.open.values())
+            .filter(|exposure| exposure.product_id == product_id && exposure.buy == buy)
+            .map(|exposure| exposure.remaining)
+            .sum()
+    }
+}
+
+#[derive(Clone, Default)]
+struct Limits {
+    max_notional: Option<f64>,
+    max_position: HashMap<String, f64>,
+    collar: Option<f64>,
+    max_orders_per_second: Option<u32>,
+}
+
+/// Pre-trade checks of [place_limit_order](../client/struct.AuthenticatedClient.html#method.place_limit_order) and
+/// [place_market_order](../client/struct.AuthenticatedClient.html#method.place_market_order) of a client
+/// created with [with_risk_gate](../client/struct.AuthenticatedClient.html#method.with_risk_gate).
+///
+/// Orders are checked when sent, a rejected order fails with a [RiskViolation](enum.RiskViolation.html) without
+/// reaching Coinbase. Best bid and ask and positions are set by hand or kept current with `update` from the `ticker`
+/// and `user` channels. Orders accepted by Coinbase stay open for the position limit until their `done` message is
+/// passed to `update` or `order_done` is called. Clones share their state.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+/// use cbpro::risk::RiskGate;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let gate = RiskGate::new()
+///     .max_notional(10_000.00)
+///     .max_position("BTC-USD", 2.0)
+///     .collar(0.05)
+///     .max_orders_per_second(5);
+/// gate.set_quote("BTC-USD", 7000.00, 7000.01);
+///
+/// let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL).with_risk_gate(gate.clone());
+/// let result = client.place_limit_order("BTC-USD", "buy", 70000.00, 1.0).json::<serde_json::Value>().await;
+///
+/// if let Err(error) = result {
+///     println!("{:?}", error.risk());
+/// }
+/// gate.kill();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct RiskGate {
+    limits: Limits,
+    state: Arc<Mutex<State>>,
+}
+
+impl RiskGate {
+    /// Creates a gate without limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects orders worth more than `value` in quote currency.
+    pub fn max_notional(mut self, value: f64) -> Self {
+        self.limits.max_notional = Some(value);
+        self
+    }
+
+    /// Rejects orders that could take the position of a product beyond `value` long or short,
+    /// counting the remaining size of open orders on the same side as filled.
+    pub fn max_position<P: Into<String>>(mut self, product_id: P, value: f64) -> Self {
+        self.limits.max_position.insert(product_id.into(), value);
+        self
+    }
+
+    /// Rejects buys priced more than `fraction` above the best ask and sells more than `fraction` below the best bid.
+    pub fn collar(mut self, fraction: f64) -> Self {
+        self.limits.collar = Some(fraction);
+        self
+    }
+
+    /// Rejects orders beyond `value` within any second.
+    pub fn max_orders_per_second(mut self, value: u32) -> Self {
+        self.limits.max_orders_per_second = Some(value);
+        self
+    }
+
+    /// Rejects all orders until `resume` is called, orders built before included.
+    /// # Example
+    ///
+    /// ```
+    /// # use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+    /// use cbpro::risk::{RiskGate, RiskViolation};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+    /// let gate = RiskGate::new();
+    /// let order = client.with_risk_gate(gate.clone()).place_limit_order("BTC-USD", "buy", 7000.00, 1.0);
+    ///
+    /// gate.kill();
+    /// let result = order.json::<serde_json::Value>().await;
+    /// assert!(matches!(result.unwrap_err().risk(), Some(RiskViolation::KillSwitch)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn kill(&self) {
+        self.state.lock().unwrap().killed = true;
+    }
+
+    /// Releases the kill switch.
+    pub fn resume(&self) {
+        self.state.lock().unwrap().killed = false;
+    }
+
+    /// Returns whether the kill switch is engaged.
+    pub fn is_killed(&self) -> bool {
+        self.state.lock().unwrap().killed
+    }
+
+    /// Sets the best bid and ask of a product.
+    pub fn set_quote(&self, product_id: &str, bid: f64, ask: f64) {
+        self.state.lock().unwrap().quotes.insert(product_id.to_string(), (bid, ask));
+    }
+
+    /// Sets the position of a product in base currency, negative when short.
+    pub fn set_position(&self, product_id: &str, size: f64) {
+        self.state.lock().unwrap().positions.insert(product_id.to_string(), size);
+    }
+
+    /// Returns the position of a product.
+    pub fn position(&self, product_id: &str) -> f64 {
+        self.state.lock().unwrap().positions.get(product_id).cloned().unwrap_or(0.0)
+    }
+
+    /// Returns the remaining size of open orders of a product on a side.
+    pub fn open_size(&self, product_id: &str, side: &str) -> f64 {
+        self.state.lock().unwrap().open_size(product_id, side == "buy")
+    }
+
+    /// Stops counting an order as open, for orders known to be done without the `user` channel.
+    pub fn order_done(&self, order_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.open.remove(order_id).is_none() {
+            state.recent_done.push_back(order_id.to_string());
+            if state.recent_done.len() > RECENT_DONE {
+                state.recent_done.pop_front();
+            }
+        }
+    }
+
+    /// Applies a message of the websocket feed, `ticker` messages set the quote, own `match` messages
+    /// of the `user` channel move the position and own `done` messages close orders. Returns whether the message was used.
+    pub fn update(&self, value: &serde_json::Value) -> crate::error::Result<bool> {
+        let message: FeedMessage = serde_json::from_value(value.clone())?;
+        let product_id = match message.product_id {
+            Some(ref product_id) => product_id,
+            None => return Ok(false),
+        };
+        match (message.type_.as_str(), message.best_bid, message.best_ask, message.size) {
+            ("ticker", Some(bid), Some(ask), _) => {
+                self.state.lock().unwrap().quotes.insert(product_id.clone(), (bid, ask));
+                Ok(true)
+            }
+            ("match", _, _, Some(size)) if message.is_own() => {
+                let order_id = if message.is_taker() { &message.taker_order_id } else { &message.maker_order_id };
+                let buy = message.own_side() == "buy";
+                let mut state = self.state.lock().unwrap();
+                *state.positions.entry(product_id.clone()).or_insert(0.0) += if buy { size } else { -size };
+                if let Some(exposure) = order_id.as_ref().and_then(|order_id| state.open.get_mut(order_id)) {
+                    exposure.remaining = (exposure.remaining - size).max(0.0);
+                }
+                Ok(true)
+            }
+            ("done", _, _, _) if message.is_own() => match message.order_id {
+                Some(ref order_id) => {
+                    self.order_done(order_id);
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+            _ => Ok(false),
+        }
+    }
+
+    /// Checks an order about to be sent and counts it as open until `settle`.
+    pub(crate) fn reserve(&self, params: &CBParams) -> crate::error::Result<u64> {
+        let (product_id, side) = match (params.product_id, params.side) {
+            (Some(product_id), Some(side)) => (product_id, side),
+            _ => return Err(Error::new(Kind::Risk, Some("order without product id or side"))),
+        };
+        let qty = match (params.size, params.funds) {
+            (Some(size), _) => QTY::Size(size),
+            (None, Some(funds)) => QTY::Funds(funds),
+            (None, None) => return Err(Error::new(Kind::Risk, Some("order without size or funds"))),
+        };
+        let price = if params.type_ == Some("limit") { params.price } else { None };
+
+        let mut state = self.state.lock().unwrap();
+        let size = self.check(&mut state, product_id, side, price, qty).map_err(|violation| Error::new(Kind::Risk, Some(violation)))?;
+        if self.limits.max_orders_per_second.is_some() {
+            state.placed.push_back(Instant::now());
+        }
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let exposure = Exposure { product_id: product_id.to_string(), buy: side == "buy", remaining: size.unwrap_or(0.0) };
+        state.pending.insert(ticket, exposure);
+        Ok(ticket)
+    }
+
+    /// Keeps a reserved order open under the id of the placement response, or drops it when placement failed.
+    pub(crate) fn settle(&self, ticket: u64, response: Option<&str>) {
+        let mut state = self.state.lock().unwrap();
+        let exposure = match state.pending.remove(&ticket) {
+            Some(exposure) => exposure,
+            None => return,
+        };
+        let order_id = response
+            .and_then(|body| serde_json::from_str::<serde_json::Value>(body).ok())
+            .and_then(|order| order["id"].as_str().map(String::from));
+        if let Some(order_id) = order_id {
+            match state.recent_done.iter().position(|done| *done == order_id) {
+                Some(index) => {
+                    state.recent_done.remove(index);
+                }
+                None => {
+                    state.open.insert(order_id, exposure);
+                }
+            }
+        }
+    }
+
+    /// Returns the estimated size of the order or the violation it causes.
+    fn check(&self, state: &mut State, product_id: &str, side: &str, price: Option<f64>, qty: QTY) -> Result<Option<f64>, RiskViolation> {
+        let limits = &self.limits;
+        if state.killed {
+            return Err(RiskViolation::KillSwitch);
+        }
+
+        if let Some(limit) = limits.max_orders_per_second {
+            let now = Instant::now();
+            while matches!(state.placed.front(), Some(at) if now.duration_since(*at) >= Duration::from_secs(1)) {
+                state.placed.pop_front();
+            }
+            if state.placed.len() >= limit as usize {
+                return Err(RiskViolation::RateLimit { limit });
+            }
+        }
+
+        let quote = state.quotes.get(product_id).cloned();
+        // market orders are valued at the touch they take
+        let reference = quote.map(|(bid, ask)| if side == "buy" { ask } else { bid });
+        let no_reference = || RiskViolation::NoReferencePrice { product_id: product_id.to_string() };
+
+        if let (Some(fraction), Some(price)) = (limits.collar, price) {
+            let reference = match reference {
+                Some(reference) => reference,
+                None => return Err(no_reference()),
+            };
+            let outside = if side == "buy" {
+                price > reference * (1.0 + fraction)
+            } else {
+                price < reference * (1.0 - fraction)
+            };
+            if outside {
+                return Err(RiskViolation::PriceCollar { price, reference });
+            }
+        }
+
+        let price = price.or(reference);
+        let (size, notional) = match qty {
+            QTY::Size(size) => (Some(size), price.map(|price| size * price)),
+            QTY::Funds(funds) => (price.map(|price| funds / price), Some(funds)),
+        };
+
+        if let Some(limit) = limits.max_notional {
+            match notional {
+                Some(notional) if notional > limit => return Err(RiskViolation::MaxNotional { notional, limit }),
+                Some(_) => (),
+                None => return Err(no_reference()),
+            }
+        }
+
+        if let Some(&limit) = limits.max_position.get(product_id) {
+            let size = match size {
+                Some(size) => size,
+                None => return Err(no_reference()),
+            };
+            let current = state.positions.get(product_id).cloned().unwrap_or(0.0);
+            let open = state.open_size(product_id, side == "buy");
+            let position = if side == "buy" { current + open + size } else { current - open - size };
+            if position.abs() > limit {
+                return Err(RiskViolation::MaxPosition { position, limit });
+            }
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit_order(product_id: &'static str, side: &'static str, price: f64, size: f64) -> CBParams<'static> {
+        let mut params = CBParams::new();
+        params.type_ = Some("limit");
+        params.product_id = Some(product_id);
+        params.side = Some(side);
+        params.price = Some(price);
+        params.size = Some(size);
+        params
+    }
+
+    fn violation(result: crate::error::Result<u64>) -> RiskViolation {
+        result.unwrap_err().risk().cloned().unwrap()
+    }
+
+    #[test]
+    fn placed_orders_are_only_recorded_with_a_rate_limit() {
+        let gate = RiskGate::new();
+        for _ in 0..10 {
+            gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0)).unwrap();
+        }
+        assert!(gate.state.lock().unwrap().placed.is_empty());
+    }
+
+    #[test]
+    fn rate_limit() {
+        let gate = RiskGate::new().max_orders_per_second(2);
+        gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0)).unwrap();
+        gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0)).unwrap();
+        let result = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0));
+        assert_eq!(violation(result), RiskViolation::RateLimit { limit: 2 });
+    }
+
+    #[test]
+    fn max_notional() {
+        let gate = RiskGate::new().max_notional(1000.0);
+        gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 10.0)).unwrap();
+        let result = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 11.0));
+        assert_eq!(violation(result), RiskViolation::MaxNotional { notional: 1100.0, limit: 1000.0 });
+    }
+
+    #[test]
+    fn max_position_counts_filled_position() {
+        let gate = RiskGate::new().max_position("BTC-USD", 2.0);
+        gate.set_position("BTC-USD", 1.5);
+        let result = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0));
+        assert_eq!(violation(result), RiskViolation::MaxPosition { position: 2.5, limit: 2.0 });
+        // selling reduces the position
+        gate.reserve(&limit_order("BTC-USD", "sell", 100.0, 3.0)).unwrap();
+    }
+
+    #[test]
+    fn max_position_counts_open_orders() {
+        let gate = RiskGate::new().max_position("BTC-USD", 2.0);
+        let first = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0)).unwrap();
+        gate.settle(first, Some(r#"{"id": "order-1"}"#));
+        // in flight, not answered yet
+        gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0)).unwrap();
+        assert_eq!(gate.open_size("BTC-USD", "buy"), 2.0);
+
+        let result = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0));
+        assert_eq!(violation(result), RiskViolation::MaxPosition { position: 3.0, limit: 2.0 });
+        // open buys do not count against sells
+        gate.reserve(&limit_order("BTC-USD", "sell", 100.0, 1.0)).unwrap();
+    }
+
+    #[test]
+    fn fills_and_done_messages_release_open_orders() {
+        let gate = RiskGate::new().max_position("BTC-USD", 2.0);
+        let ticket = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 2.0)).unwrap();
+        gate.settle(ticket, Some(r#"{"id": "order-1"}"#));
+
+        let fill = serde_json::json!({
+            "type": "match", "product_id": "BTC-USD", "side": "buy", "size": "0.5", "price": "100",
+            "maker_order_id": "order-1", "taker_order_id": "other", "user_id": "me"
+        });
+        assert!(gate.update(&fill).unwrap());
+        assert_eq!(gate.position("BTC-USD"), 0.5);
+        assert_eq!(gate.open_size("BTC-USD", "buy"), 1.5);
+
+        let done = serde_json::json!({"type": "done", "product_id": "BTC-USD", "order_id": "order-1", "user_id": "me"});
+        assert!(gate.update(&done).unwrap());
+        assert_eq!(gate.open_size("BTC-USD", "buy"), 0.0);
+    }
+
+    #[test]
+    fn failed_placement_releases_the_reservation() {
+        let gate = RiskGate::new();
+        let ticket = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0)).unwrap();
+        gate.settle(ticket, None);
+        assert_eq!(gate.open_size("BTC-USD", "buy"), 0.0);
+    }
+
+    #[test]
+    fn done_before_settle_is_not_kept_open() {
+        let gate = RiskGate::new();
+        let ticket = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0)).unwrap();
+        gate.order_done("order-1");
+        gate.settle(ticket, Some(r#"{"id": "order-1"}"#));
+        assert_eq!(gate.open_size("BTC-USD", "buy"), 0.0);
+        assert!(gate.state.lock().unwrap().recent_done.is_empty());
+    }
+
+    #[test]
+    fn price_collar() {
+        let gate = RiskGate::new().collar(0.05);
+        let result = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0));
+        assert_eq!(violation(result), RiskViolation::NoReferencePrice { product_id: "BTC-USD".to_string() });
+
+        gate.set_quote("BTC-USD", 99.0, 100.0);
+        gate.reserve(&limit_order("BTC-USD", "buy", 105.0, 1.0)).unwrap();
+        let result = gate.reserve(&limit_order("BTC-USD", "buy", 105.5, 1.0));
+        assert_eq!(violation(result), RiskViolation::PriceCollar { price: 105.5, reference: 100.0 });
+        let result = gate.reserve(&limit_order("BTC-USD", "sell", 94.0, 1.0));
+        assert_eq!(violation(result), RiskViolation::PriceCollar { price: 94.0, reference: 99.0 });
+    }
+
+    #[test]
+    fn kill_switch() {
+        let gate = RiskGate::new();
+        gate.kill();
+        let result = gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0));
+        assert_eq!(violation(result), RiskViolation::KillSwitch);
+        gate.resume();
+        gate.reserve(&limit_order("BTC-USD", "buy", 100.0, 1.0)).unwrap();
+    }
+}
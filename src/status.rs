@@ -0,0 +1,133 @@
+use std::collections::{HashMap, VecDeque};
+use futures::stream::{self, BoxStream, StreamExt};
+use crate::models::{ChannelMessage, StatusMessage, StatusProduct};
+use crate::websocket::WebSocketFeed;
+
+/// Stream of [StatusTransition](struct.StatusTransition.html) returned by [transitions](fn.transitions.html)
+pub type TransitionStream = BoxStream<'static, crate::error::Result<StatusTransition>>;
+
+/// Trading status of a product
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradingStatus {
+    /// e.g. `online`, `offline` or `delisted`
+    pub status: String,
+    pub post_only: bool,
+    pub limit_only: bool,
+    pub cancel_only: bool,
+    pub auction_mode: bool,
+    pub status_message: Option<String>,
+}
+
+impl From<&StatusProduct> for TradingStatus {
+    fn from(product: &StatusProduct) -> Self {
+        Self {
+            status: product.status.clone(),
+            post_only: product.post_only,
+            limit_only: product.limit_only,
+            cancel_only: product.cancel_only,
+            auction_mode: product.auction_mode,
+            status_message: product.status_message.clone(),
+        }
+    }
+}
+
+/// Change of the trading status of a product
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusTransition {
+    pub product_id: String,
+    pub from: TradingStatus,
+    pub to: TradingStatus,
+}
+
+/// Last trading status of every product seen on the `status` channel.
+#[derive(Debug, Clone, Default)]
+pub struct StatusTracker {
+    products: HashMap<String, TradingStatus>,
+}
+
+impl StatusTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the last status of a product.
+    pub fn status(&self, product_id: &str) -> Option<&TradingStatus> {
+        self.products.get(product_id)
+    }
+
+    /// Records the products of a `status` message and returns the products whose status changed.
+    /// Products seen for the first time are recorded without a transition.
+    /// # Example
+    ///
+    /// ```
+    /// use cbpro::models::StatusMessage;
+    /// use cbpro::status::StatusTracker;
+    ///
+    /// let message = |post_only: bool| -> StatusMessage {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "type": "status",
+    ///         "products": [{
+    ///             "id": "BTC-USD", "base_currency": "BTC", "quote_currency": "USD",
+    ///             "status": "online", "post_only": post_only, "limit_only": false, "cancel_only": false
+    ///         }],
+    ///         "currencies": []
+    ///     })).unwrap()
+    /// };
+    ///
+    /// let mut tracker = StatusTracker::new();
+    /// assert!(tracker.update(&message(false)).is_empty());
+    ///
+    /// let transitions = tracker.update(&message(true));
+    /// assert_eq!(transitions.len(), 1);
+    /// assert!(!transitions[0].from.post_only);
+    /// assert!(transitions[0].to.post_only);
+    /// ```
+    pub fn update(&mut self, message: &StatusMessage) -> Vec<StatusTransition> {
+        let mut transitions = Vec::new();
+        for product in &message.products {
+            let to = TradingStatus::from(product);
+            match self.products.insert(product.id.clone(), to.clone()) {
+                Some(from) if from != to => transitions.push(StatusTransition { product_id: product.id.clone(), from, to }),
+                _ => (),
+            }
+        }
+        transitions
+    }
+}
+
+/// Streams trading status transitions from a feed subscribed to the `status` channel, other messages are skipped.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::status::transitions;
+/// use cbpro::websocket::{Channels, WebSocketFeed, SANDBOX_FEED_URL};
+/// use futures::TryStreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut feed = WebSocketFeed::connect(SANDBOX_FEED_URL).await?;
+/// feed.subscribe(&[], &[Channels::STATUS]).await?;
+///
+/// let mut transitions = transitions(feed);
+/// while let Some(transition) = transitions.try_next().await? {
+///     println!("{} {:?} -> {:?}", transition.product_id, transition.from, transition.to);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn transitions(feed: WebSocketFeed) -> TransitionStream {
+    let state = (feed, StatusTracker::new(), VecDeque::new());
+    stream::try_unfold(state, |(mut feed, mut tracker, mut pending)| async move {
+        loop {
+            if let Some(transition) = pending.pop_front() {
+                return Ok(Some((transition, (feed, tracker, pending))));
+            }
+            match feed.json::<ChannelMessage>().await? {
+                Some(ChannelMessage::Status(message)) => pending.extend(tracker.update(&message)),
+                Some(_) => (),
+                None => return Ok(None),
+            }
+        }
+    })
+    .boxed()
+}
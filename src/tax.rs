@@ -0,0 +1,269 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use chrono::{offset::TimeZone, DateTime, Utc};
+use futures::TryStreamExt;
+use serde::Serialize;
+use crate::client::AuthenticatedClient;
+use crate::models::{Account, LedgerEntry};
+
+/// Currencies treated as cash, no lots are tracked for them
+pub const FIAT: &[&str] = &["USD", "EUR", "GBP"];
+
+const EPSILON: f64 = 1e-12;
+
+/// Ledger of a single account
+#[derive(Debug, Clone)]
+pub struct AccountLedger {
+    pub account_id: String,
+    pub currency: String,
+    /// Entries in any order
+    pub entries: Vec<LedgerEntry>,
+}
+
+/// Lot of a currency, the disposal fields are set once it is sold, converted or withdrawn
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaxLot {
+    pub currency: String,
+    pub size: f64,
+    /// `None` for lots acquired before the exported range
+    pub acquired_at: Option<DateTime<Utc>>,
+    /// Ledger entry type of the acquisition
+    pub acquired_via: Option<String>,
+    /// Trade, transfer or conversion id of the acquisition
+    pub acquired_ref: Option<String>,
+    /// Cost including fees, `None` for deposits and lots acquired before the exported range
+    pub cost_basis: Option<f64>,
+    pub cost_currency: Option<String>,
+    pub disposed_at: Option<DateTime<Utc>>,
+    /// Ledger entry type of the disposal
+    pub disposed_via: Option<String>,
+    /// Trade, transfer or conversion id of the disposal
+    pub disposed_ref: Option<String>,
+    /// Proceeds net of fees, `None` for open lots, withdrawals and fees
+    pub proceeds: Option<f64>,
+    pub proceeds_currency: Option<String>,
+    /// Set when cost basis and proceeds are known and in the same currency
+    pub gain: Option<f64>,
+}
+
+struct Event<'a> {
+    currency: &'a str,
+    entry: &'a LedgerEntry,
+}
+
+/// Counterpart amounts of a trade or conversion, keyed by currency
+type Legs<'a> = HashMap<String, Vec<(&'a str, f64)>>;
+
+fn sum(legs: &Legs, key: &str, currency: &str) -> Option<(String, f64)> {
+    let legs = legs.get(key)?;
+    let (counter, _) = legs.iter().find(|(leg, _)| *leg != currency)?;
+    let amount = legs.iter().filter(|(leg, _)| leg == counter).map(|(_, amount)| amount).sum();
+    Some((counter.to_string(), amount))
+}
+
+fn acquire(lots: &mut VecDeque<TaxLot>, event: &Event, reference: Option<String>, cost: Option<(String, f64)>) {
+    let (cost_currency, cost_basis) = match cost {
+        Some((currency, cost)) => (Some(currency), Some(cost)),
+        None => (None, None),
+    };
+    lots.push_back(TaxLot {
+        currency: event.currency.to_string(),
+        size: event.entry.amount,
+        acquired_at: Some(event.entry.created_at),
+        acquired_via: Some(event.entry.type_.clone()),
+        acquired_ref: reference,
+        cost_basis,
+        cost_currency,
+        disposed_at: None,
+        disposed_via: None,
+        disposed_ref: None,
+        proceeds: None,
+        proceeds_currency: None,
+        gain: None,
+    });
+}
+
+fn dispose(lots: &mut VecDeque<TaxLot>, closed: &mut Vec<TaxLot>, event: &Event, reference: Option<String>, proceeds: Option<(String, f64)>) {
+    let size = -event.entry.amount;
+    let mut remaining = size;
+
+    while remaining > EPSILON {
+        let mut lot = match lots.front_mut() {
+            Some(open) => {
+                let taken = remaining.min(open.size);
+                let mut lot = open.clone();
+                lot.size = taken;
+                lot.cost_basis = open.cost_basis.map(|cost| cost * taken / open.size);
+                open.cost_basis = open.cost_basis.map(|cost| cost - lot.cost_basis.unwrap());
+                open.size -= taken;
+                if open.size <= EPSILON {
+                    lots.pop_front();
+                }
+                lot
+            }
+            // acquired before the exported range
+            None => TaxLot {
+                currency: event.currency.to_string(),
+                size: remaining,
+                acquired_at: None,
+                acquired_via: None,
+                acquired_ref: None,
+                cost_basis: None,
+                cost_currency: None,
+                disposed_at: None,
+                disposed_via: None,
+                disposed_ref: None,
+                proceeds: None,
+                proceeds_currency: None,
+                gain: None,
+            },
+        };
+        remaining -= lot.size;
+
+        lot.disposed_at = Some(event.entry.created_at);
+        lot.disposed_via = Some(event.entry.type_.clone());
+        lot.disposed_ref = reference.clone();
+        if let Some((ref currency, amount)) = proceeds {
+            lot.proceeds = Some(amount * lot.size / size);
+            lot.proceeds_currency = Some(currency.clone());
+        }
+        if let (Some(cost), Some(proceeds)) = (lot.cost_basis, lot.proceeds) {
+            if lot.cost_currency == lot.proceeds_currency {
+                lot.gain = Some(proceeds - cost);
+            }
+        }
+        closed.push(lot);
+    }
+}
+
+/// Matches acquisitions and disposals of every non fiat currency first in, first out.
+///
+/// Trades are valued by the counterpart entry of the same trade in another ledger, fees in that currency
+/// are added to the cost or deducted from the proceeds. Conversions are valued the same way through the conversion id,
+/// deposits have no cost basis and withdrawals no proceeds.
+///
+/// Returns closed lots in order of disposal followed by the open lots ordered by currency.
+pub fn tax_lots(ledgers: &[AccountLedger], fiat: &[&str]) -> Vec<TaxLot> {
+    let mut events: Vec<Event> = ledgers
+        .iter()
+        .flat_map(|ledger| ledger.entries.iter().map(move |entry| Event { currency: &ledger.currency, entry }))
+        .collect();
+    events.sort_by(|a, b| {
+        let id = |event: &Event| event.entry.id.parse::<u64>().unwrap_or(0);
+        a.entry.created_at.cmp(&b.entry.created_at).then_with(|| id(a).cmp(&id(b)))
+    });
+
+    let mut trades = Legs::new();
+    let mut fees = Legs::new();
+    let mut conversions = Legs::new();
+    for event in &events {
+        let details = &event.entry.details;
+        let leg = (event.currency, event.entry.amount);
+        match (event.entry.type_.as_str(), &details.product_id, &details.trade_id, &details.conversion_id) {
+            ("match", Some(product_id), Some(trade_id), _) => {
+                trades.entry(format!("{}:{}", product_id, trade_id)).or_default().push(leg)
+            }
+            ("fee", Some(product_id), Some(trade_id), _) => {
+                fees.entry(format!("{}:{}", product_id, trade_id)).or_default().push(leg)
+            }
+            ("conversion", _, _, Some(conversion_id)) => conversions.entry(conversion_id.clone()).or_default().push(leg),
+            _ => (),
+        }
+    }
+
+    let mut open: BTreeMap<&str, VecDeque<TaxLot>> = BTreeMap::new();
+    let mut closed = Vec::new();
+    for event in &events {
+        if fiat.contains(&event.currency) {
+            continue;
+        }
+        let lots = open.entry(event.currency).or_default();
+        let details = &event.entry.details;
+        let acquired = event.entry.amount > 0.0;
+
+        let (reference, value) = match event.entry.type_.as_str() {
+            "match" => {
+                let key = format!("{}:{}", details.product_id.as_deref().unwrap_or(""), details.trade_id.as_deref().unwrap_or(""));
+                let value = sum(&trades, &key, event.currency).map(|(currency, amount)| {
+                    let fee = fees
+                        .get(&key)
+                        .map(|legs| legs.iter().filter(|(leg, _)| *leg == currency).map(|(_, fee)| -fee).sum())
+                        .unwrap_or(0.0);
+                    let amount = if acquired { -amount + fee } else { amount - fee };
+                    (currency, amount)
+                });
+                (details.trade_id.clone(), value)
+            }
+            "conversion" => {
+                let value = details.conversion_id
+                    .as_ref()
+                    .and_then(|id| sum(&conversions, id, event.currency))
+                    .map(|(currency, amount)| (currency, amount.abs()));
+                (details.conversion_id.clone(), value)
+            }
+            "rebate" => (details.trade_id.clone(), Some((event.currency.to_string(), 0.0))),
+            "transfer" => (details.transfer_id.clone(), None),
+            _ => (details.trade_id.clone(), None),
+        };
+
+        if acquired {
+            acquire(lots, event, reference, value);
+        } else {
+            dispose(lots, &mut closed, event, reference, value);
+        }
+    }
+
+    closed.extend(open.into_values().flatten());
+    closed
+}
+
+/// Writes lots as csv with a header row.
+pub fn write_csv<W: Write>(lots: &[TaxLot], writer: W) -> crate::error::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for lot in lots {
+        writer.serialize(lot)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Fetches the ledgers of all accounts between the given dates.
+///
+/// Lots acquired before `start_date` have no cost basis, start at the first activity of the profile for complete results.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+/// use cbpro::tax::{fetch_ledgers, tax_lots, write_csv, FIAT};
+/// use chrono::{ TimeZone, Utc };
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+/// let start_date = Utc.ymd(2019, 1, 1).and_hms(0, 0, 0);
+/// let end_date = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+///
+/// let ledgers = fetch_ledgers(&client, start_date, end_date).await?;
+/// let lots = tax_lots(&ledgers, FIAT);
+/// write_csv(&lots, std::fs::File::create("lots.csv")?)?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn fetch_ledgers<Tz: TimeZone>(client: &AuthenticatedClient, start_date: DateTime<Tz>, end_date: DateTime<Tz>) -> crate::error::Result<Vec<AccountLedger>>
+where
+    Tz::Offset: core::fmt::Display,
+{
+    let accounts: Vec<Account> = client.list_accounts().json().await?;
+    let mut ledgers = Vec::with_capacity(accounts.len());
+    for account in accounts {
+        let entries = client
+            .get_account_history(&account.id)
+            .start_date(start_date.clone())
+            .end_date(end_date.clone())
+            .paginate_items::<LedgerEntry>()?
+            .try_collect()
+            .await?;
+        ledgers.push(AccountLedger { account_id: account.id, currency: account.currency, entries });
+    }
+    Ok(ledgers)
+}
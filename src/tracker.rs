@@ -0,0 +1,468 @@
+use core::time::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+use crate::client::{AuthenticatedClient, FILL, ORD};
+use crate::models::{FeedMessage, Fill, Order};
+use crate::websocket::{Channels, WebSocketFeed};
+
+/// Lifecycle state of a tracked order
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderStatus {
+    /// Registered but not yet seen on the exchange, or received and not yet on the book
+    Pending,
+    /// Resting on the book without fills
+    Open,
+    /// Filled in part and still active
+    PartiallyFilled,
+    /// No longer active, with the reason given by Coinbase, e.g. `filled` or `canceled`
+    Done(String),
+}
+
+/// Last known state of an order registered with the [OrderTracker](struct.OrderTracker.html)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedOrder {
+    pub order_id: Option<String>,
+    pub client_oid: Option<String>,
+    pub product_id: Option<String>,
+    pub side: Option<String>,
+    pub price: Option<f64>,
+    pub size: Option<f64>,
+    pub status: OrderStatus,
+    pub filled_size: f64,
+    /// Quote amount of all fills excluding fees
+    pub executed_value: f64,
+    /// Exact once done, estimated from the taker fee rate of `match` messages before
+    pub fees: f64,
+}
+
+impl TrackedOrder {
+    fn new(order_id: Option<String>, client_oid: Option<String>) -> Self {
+        Self {
+            order_id,
+            client_oid,
+            product_id: None,
+            side: None,
+            price: None,
+            size: None,
+            status: OrderStatus::Pending,
+            filled_size: 0.0,
+            executed_value: 0.0,
+            fees: 0.0,
+        }
+    }
+
+    /// Returns the average price of all fills.
+    pub fn average_price(&self) -> Option<f64> {
+        if self.filled_size > 0.0 {
+            Some(self.executed_value / self.filled_size)
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether the order is no longer active.
+    pub fn is_done(&self) -> bool {
+        matches!(self.status, OrderStatus::Done(_))
+    }
+
+    fn active(&self) -> OrderStatus {
+        if self.filled_size > 0.0 {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::Open
+        }
+    }
+
+    fn sync(&mut self, order: Order) {
+        self.order_id = Some(order.id);
+        if order.client_oid.is_some() {
+            self.client_oid = order.client_oid;
+        }
+        self.product_id = Some(order.product_id);
+        self.side = Some(order.side);
+        self.price = order.price;
+        self.size = order.size;
+        self.filled_size = order.filled_size;
+        self.executed_value = order.executed_value;
+        self.fees = order.fill_fees;
+        self.status = match order.status.as_str() {
+            "done" => OrderStatus::Done(order.done_reason.unwrap_or_else(|| "filled".to_string())),
+            "rejected" => OrderStatus::Done("rejected".to_string()),
+            "open" | "active" => self.active(),
+            _ => OrderStatus::Pending,
+        };
+    }
+}
+
+/// Tracks registered orders through the `user` channel of the websocket feed.
+///
+/// While the socket is down orders are polled with `get_order`, after every (re)connect all active orders
+/// are reconciled over REST. The REST state wins, `match` messages with a trade id already covered by the last poll
+/// of an order are ignored. A fill landing between the order and fills request is picked up by the next poll.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::client::{AuthenticatedClient, ORD, SANDBOX_URL};
+/// use cbpro::tracker::OrderTracker;
+/// use cbpro::websocket::SANDBOX_FEED_URL;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+/// let mut tracker = OrderTracker::new(client.clone(), SANDBOX_FEED_URL, &["BTC-USD"]);
+///
+/// client.place_limit_order("BTC-USD", "buy", 7000.00, 0.01)
+///     .client_oid("00000000-0000-0000-0000-000000000001")
+///     .json::<serde_json::Value>()
+///     .await?;
+/// tracker.track(ORD::ClientOID("00000000-0000-0000-0000-000000000001"));
+///
+/// loop {
+///     let order = tracker.next().await?;
+///     println!("{:?} filled {} @ {:?}", order.status, order.filled_size, order.average_price());
+///     if order.is_done() {
+///         break;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct OrderTracker {
+    client: AuthenticatedClient,
+    url: String,
+    product_ids: Vec<String>,
+    feed: Option<WebSocketFeed>,
+    orders: Vec<TrackedOrder>,
+    /// Highest trade id reflected in the state of an order, by order id
+    applied: HashMap<String, u64>,
+    updates: VecDeque<TrackedOrder>,
+    poll_interval: Duration,
+    reconnect_interval: Duration,
+    stale_after: Duration,
+    connected_at: Option<Instant>,
+}
+
+impl OrderTracker {
+    /// Creates a tracker for orders of the given products, the feed is connected on the first call to `next`.
+    pub fn new<U: Into<String>>(client: AuthenticatedClient, feed_url: U, product_ids: &[&str]) -> Self {
+        Self {
+            client,
+            url: feed_url.into(),
+            product_ids: product_ids.iter().map(|id| id.to_string()).collect(),
+            feed: None,
+            orders: Vec::new(),
+            applied: HashMap::new(),
+            updates: VecDeque::new(),
+            poll_interval: Duration::from_secs(5),
+            reconnect_interval: Duration::from_secs(10),
+            stale_after: Duration::from_secs(30),
+            connected_at: None,
+        }
+    }
+
+    /// Sets the delay between REST polls while the socket is down, defaults to 5 seconds.
+    pub fn poll_interval(mut self, value: Duration) -> Self {
+        self.poll_interval = value;
+        self
+    }
+
+    /// Sets the delay between connection attempts, defaults to 10 seconds.
+    pub fn reconnect_interval(mut self, value: Duration) -> Self {
+        self.reconnect_interval = value;
+        self
+    }
+
+    /// Sets the time without any message after which the socket is considered down, defaults to 30 seconds.
+    pub fn stale_after(mut self, value: Duration) -> Self {
+        self.stale_after = value;
+        self
+    }
+
+    /// Registers an order by order id or client oid.
+    pub fn track(&mut self, ord: ORD) {
+        if self.find(&ord).is_some() {
+            return;
+        }
+        let order = match ord {
+            ORD::OrderID(id) => TrackedOrder::new(Some(id.to_string()), None),
+            ORD::ClientOID(id) => TrackedOrder::new(None, Some(id.to_string())),
+        };
+        self.orders.push(order);
+    }
+
+    /// Stops tracking an order and returns its last state.
+    pub fn untrack(&mut self, ord: ORD) -> Option<TrackedOrder> {
+        let index = self.find(&ord)?;
+        let order = self.orders.remove(index);
+        if let Some(ref order_id) = order.order_id {
+            self.applied.remove(order_id);
+        }
+        Some(order)
+    }
+
+    /// Returns the last known state of an order.
+    pub fn order(&self, ord: ORD) -> Option<&TrackedOrder> {
+        self.find(&ord).map(|index| &self.orders[index])
+    }
+
+    /// Returns all tracked orders in order of registration.
+    pub fn orders(&self) -> &[TrackedOrder] {
+        &self.orders
+    }
+
+    /// Returns whether updates are currently received over the websocket feed.
+    pub fn is_connected(&self) -> bool {
+        self.feed.is_some()
+    }
+
+    /// Waits for the next change of a tracked order and returns its new state.
+    pub async fn next(&mut self) -> crate::error::Result<TrackedOrder> {
+        loop {
+            if let Some(order) = self.updates.pop_front() {
+                return Ok(order);
+            }
+
+            if let Some(mut feed) = self.feed.take() {
+                if let Ok(Ok(Some(value))) = tokio::time::timeout(self.stale_after, feed.json::<serde_json::Value>()).await {
+                    self.feed = Some(feed);
+                    self.apply(&value).await?;
+                }
+                continue;
+            }
+
+            let due = match self.connected_at {
+                Some(at) => at.elapsed() >= self.reconnect_interval,
+                None => true,
+            };
+            if due {
+                self.connected_at = Some(Instant::now());
+                self.feed = self.connect().await.ok();
+            }
+
+            self.reconcile().await?;
+            if self.feed.is_none() && self.updates.is_empty() {
+                tokio::time::delay_for(self.poll_interval).await;
+            }
+        }
+    }
+
+    async fn connect(&self) -> crate::error::Result<WebSocketFeed> {
+        let auth = self.client.auth();
+        let mut feed = WebSocketFeed::connect_auth(auth.key.clone(), auth.pass.clone(), auth.secret.clone(), self.url.clone()).await?;
+        let product_ids: Vec<&str> = self.product_ids.iter().map(String::as_str).collect();
+        // heartbeats keep the feed busy so a silent socket is detected as stale
+        feed.subscribe(&product_ids, &[Channels::USER, Channels::HEARTBEAT]).await?;
+        Ok(feed)
+    }
+
+    fn find(&self, ord: &ORD) -> Option<usize> {
+        self.orders.iter().position(|order| match *ord {
+            ORD::OrderID(id) => order.order_id.as_deref() == Some(id),
+            ORD::ClientOID(id) => order.client_oid.as_deref() == Some(id),
+        })
+    }
+
+    /// Polls all active orders over REST.
+    async fn reconcile(&mut self) -> crate::error::Result<()> {
+        for index in 0..self.orders.len() {
+            if !self.orders[index].is_done() {
+                self.poll(index).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn poll(&mut self, index: usize) -> crate::error::Result<()> {
+        let before = self.orders[index].clone();
+        self.refresh(index).await?;
+
+        let order = &self.orders[index];
+        if *order != before {
+            self.updates.push_back(order.clone());
+        }
+        Ok(())
+    }
+
+    async fn refresh(&mut self, index: usize) -> crate::error::Result<()> {
+        let order = &self.orders[index];
+        let response = match (&order.order_id, &order.client_oid) {
+            (Some(id), _) => self.client.get_order(ORD::OrderID(id)).json::<Order>().await,
+            (None, Some(id)) => self.client.get_order(ORD::ClientOID(id)).json::<Order>().await,
+            (None, None) => return Ok(()),
+        };
+
+        // the latest fill tells which matches are already part of the filled size
+        let last_fill = match response {
+            Ok(ref response) if response.filled_size > 0.0 => {
+                let fills = self.client.get_fills(FILL::OrderID(&response.id)).limit(1).json::<Vec<Fill>>().await?;
+                fills.first().map(|fill| fill.trade_id)
+            }
+            _ => None,
+        };
+
+        let order = &mut self.orders[index];
+        match response {
+            Ok(response) => {
+                if let Some(trade_id) = last_fill {
+                    let applied = self.applied.entry(response.id.clone()).or_insert(trade_id);
+                    *applied = (*applied).max(trade_id);
+                }
+                order.sync(response);
+            }
+            // orders canceled without fills are removed, unknown pending orders may not be received yet
+            Err(ref error) if error.status() == Some(404) => {
+                if order.status != OrderStatus::Pending {
+                    order.status = OrderStatus::Done("canceled".to_string());
+                }
+            }
+            Err(error) => return Err(error),
+        }
+        Ok(())
+    }
+
+    async fn apply(&mut self, value: &serde_json::Value) -> crate::error::Result<()> {
+        let message: FeedMessage = serde_json::from_value(value.clone())?;
+        let index = match message.type_.as_str() {
+            "received" => self.orders.iter().position(|order| {
+                (order.order_id.is_some() && order.order_id == message.order_id)
+                    || (order.client_oid.is_some() && order.client_oid == message.client_oid)
+            }),
+            "open" | "done" | "change" => self.orders.iter().position(|order| {
+                order.order_id.is_some() && order.order_id == message.order_id
+            }),
+            "match" => self.orders.iter().position(|order| {
+                order.order_id.is_some()
+                    && (order.order_id == message.maker_order_id || order.order_id == message.taker_order_id)
+            }),
+            _ => None,
+        };
+        let index = match index {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let before = self.orders[index].clone();
+        let applied = before.order_id.as_ref().and_then(|id| self.applied.get(id)).cloned();
+        let order = &mut self.orders[index];
+        match message.type_.as_str() {
+            "received" => {
+                order.order_id = message.order_id;
+                order.product_id = message.product_id;
+                order.side = message.side;
+                order.price = message.price;
+                order.size = message.size;
+            }
+            "open" => order.status = order.active(),
+            "match" => {
+                let stale = match (applied, message.trade_id) {
+                    (Some(applied), Some(trade_id)) => trade_id <= applied,
+                    _ => false,
+                };
+                if let (false, Some(price), Some(size)) = (stale, message.price, message.size) {
+                    if let (Some(order_id), Some(trade_id)) = (&order.order_id, message.trade_id) {
+                        self.applied.insert(order_id.clone(), trade_id);
+                    }
+                    order.filled_size += size;
+                    order.executed_value += price * size;
+                    if order.order_id == message.taker_order_id {
+                        order.fees += price * size * message.taker_fee_rate.unwrap_or(0.0);
+                    }
+                    order.status = order.active();
+                }
+            }
+            "change" => {
+                if let Some(size) = message.new_size {
+                    order.size = Some(size);
+                }
+            }
+            "done" => {
+                let status = OrderStatus::Done(message.reason.unwrap_or_else(|| "filled".to_string()));
+                // exact fees are only available over REST, keep the socket state if it fails or lags behind
+                if order.filled_size > 0.0 {
+                    self.refresh(index).await.ok();
+                }
+                if !self.orders[index].is_done() {
+                    self.orders[index].status = status;
+                }
+            }
+            _ => (),
+        }
+
+        let order = &self.orders[index];
+        if *order != before {
+            self.updates.push_back(order.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::client::SANDBOX_URL;
+
+    fn tracker() -> OrderTracker {
+        let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+        let mut tracker = OrderTracker::new(client, "wss://localhost", &["BTC-USD"]);
+        tracker.track(ORD::OrderID("order"));
+        tracker
+    }
+
+    fn fill(trade_id: u64, time: &str) -> serde_json::Value {
+        json!({
+            "type": "match",
+            "trade_id": trade_id,
+            "maker_order_id": "other",
+            "taker_order_id": "order",
+            "side": "sell",
+            "price": "100.00",
+            "size": "0.5",
+            "product_id": "BTC-USD",
+            "taker_fee_rate": "0.005",
+            "time": time,
+        })
+    }
+
+    #[tokio::test]
+    async fn match_covered_by_poll_is_ignored() {
+        let mut tracker = tracker();
+        tracker.applied.insert("order".to_string(), 10);
+
+        // a time far ahead of the local clock does not matter, only the trade id does
+        tracker.apply(&fill(10, "2999-01-01T00:00:00Z")).await.unwrap();
+        tracker.apply(&fill(9, "2999-01-01T00:00:00Z")).await.unwrap();
+
+        assert_eq!(tracker.orders()[0].filled_size, 0.0);
+        assert!(tracker.updates.is_empty());
+    }
+
+    #[tokio::test]
+    async fn match_after_poll_is_applied_once() {
+        let mut tracker = tracker();
+        tracker.applied.insert("order".to_string(), 10);
+
+        // a time behind the local clock is still a new fill
+        tracker.apply(&fill(11, "2000-01-01T00:00:00Z")).await.unwrap();
+        tracker.apply(&fill(11, "2000-01-01T00:00:00Z")).await.unwrap();
+
+        let order = &tracker.orders()[0];
+        assert_eq!(order.filled_size, 0.5);
+        assert_eq!(order.executed_value, 50.0);
+        assert_eq!(order.fees, 0.25);
+        assert_eq!(order.status, OrderStatus::PartiallyFilled);
+        assert_eq!(tracker.applied.get("order"), Some(&11));
+        assert_eq!(tracker.updates.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn match_without_poll_is_applied() {
+        let mut tracker = tracker();
+
+        tracker.apply(&fill(1, "2020-01-01T00:00:00Z")).await.unwrap();
+        tracker.apply(&fill(2, "2020-01-01T00:00:01Z")).await.unwrap();
+
+        assert_eq!(tracker.orders()[0].filled_size, 1.0);
+        assert_eq!(tracker.applied.get("order"), Some(&2));
+    }
+}
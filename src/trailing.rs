@@ -0,0 +1,472 @@
+use core::time::Duration;
+use std::time::Instant;
+use crate::batch::OrderRequest;
+use crate::client::{AuthenticatedClient, ORD};
+use crate::error::{Error, Kind};
+use crate::execution::snap;
+use crate::models::{FeedMessage, Order, Product, Ticker};
+use crate::replace::ReplaceOutcome;
+use crate::websocket::{Channels, WebSocketFeed};
+
+/// Distance of a [TrailingStop](struct.TrailingStop.html) from the best price seen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trail {
+    /// In quote currency
+    Absolute(f64),
+    /// In percent of the price
+    Percent(f64),
+}
+
+/// Change reported by [TrailingStop::next](struct.TrailingStop.html#method.next)
+#[derive(Debug, Clone)]
+pub enum TrailEvent {
+    /// The stop order was placed or moved
+    Moved { order_id: String, stop_price: f64 },
+    /// The stop order is done. `order` is `None` if it was canceled without fills outside of the trailing stop
+    Done { order: Option<Box<Order>> },
+}
+
+/// Stop order that follows the last trade price from the `ticker` or `matches` channel.
+///
+/// A `sell` stop protects a long position and moves up as the price rises, a `buy` stop moves down as the price falls.
+/// The stop never moves back. Each move is a [replace_order](../client/struct.AuthenticatedClient.html#method.replace_order),
+/// so the old stop is confirmed canceled before the new one is placed and moves are at least `min_reprice_interval` apart.
+/// While the feed is down the price is polled with `get_product_ticker`.
+/// # Example
+///
+/// ```no_run
+/// use cbpro::client::{AuthenticatedClient, SANDBOX_URL};
+/// use cbpro::trailing::{Trail, TrailEvent, TrailingStop};
+/// use cbpro::websocket::SANDBOX_FEED_URL;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = AuthenticatedClient::new("<key>", "<pass>", "<secret>", SANDBOX_URL);
+/// let mut stop = TrailingStop::new(client, SANDBOX_FEED_URL, "BTC-USD", "sell", 0.5, Trail::Percent(2.0))
+///     .limit_offset(10.00);
+///
+/// while let Some(event) = stop.next().await? {
+///     match event {
+///         TrailEvent::Moved { stop_price, .. } => println!("stop at {}", stop_price),
+///         TrailEvent::Done { order } => println!("done {:?}", order),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct TrailingStop {
+    client: AuthenticatedClient,
+    url: String,
+    product_id: String,
+    side: String,
+    size: f64,
+    trail: Trail,
+    limit_offset: f64,
+    channel: &'static str,
+    min_reprice_interval: Duration,
+    reconnect_interval: Duration,
+    tick: Duration,
+    product: Option<Product>,
+    feed: Option<WebSocketFeed>,
+    connected_at: Option<Instant>,
+    order_id: Option<String>,
+    stop_price: Option<f64>,
+    extreme: Option<f64>,
+    last_price: Option<f64>,
+    repriced_at: Option<Instant>,
+    finished: bool,
+}
+
+impl TrailingStop {
+    /// Creates a trailing stop of `size`, the stop is placed on the first call to `next`.
+    pub fn new<U, P, S>(client: AuthenticatedClient, feed_url: U, product_id: P, side: S, size: f64, trail: Trail) -> Self
+    where
+        U: Into<String>,
+        P: Into<String>,
+        S: Into<String>,
+    {
+        Self {
+            client,
+            url: feed_url.into(),
+            product_id: product_id.into(),
+            side: side.into(),
+            size,
+            trail,
+            limit_offset: 0.0,
+            channel: Channels::TICKER,
+            min_reprice_interval: Duration::from_secs(5),
+            reconnect_interval: Duration::from_secs(10),
+            tick: Duration::from_secs(1),
+            product: None,
+            feed: None,
+            connected_at: None,
+            order_id: None,
+            stop_price: None,
+            extreme: None,
+            last_price: None,
+            repriced_at: None,
+            finished: false,
+        }
+    }
+
+    /// Sets the distance of the limit price behind the stop price, defaults to 0.
+    pub fn limit_offset(mut self, value: f64) -> Self {
+        self.limit_offset = value;
+        self
+    }
+
+    /// Sets the channel the price is taken from, `Channels::TICKER` (default) or `Channels::MATCHES`.
+    pub fn channel(mut self, value: &'static str) -> Self {
+        self.channel = value;
+        self
+    }
+
+    /// Sets the minimum time between two moves of the stop, defaults to 5 seconds.
+    pub fn min_reprice_interval(mut self, value: Duration) -> Self {
+        self.min_reprice_interval = value;
+        self
+    }
+
+    /// Sets the delay between connection attempts, defaults to 10 seconds.
+    pub fn reconnect_interval(mut self, value: Duration) -> Self {
+        self.reconnect_interval = value;
+        self
+    }
+
+    /// Returns the id of the resting stop order.
+    pub fn order_id(&self) -> Option<&str> {
+        self.order_id.as_deref()
+    }
+
+    /// Returns the current stop price.
+    pub fn stop_price(&self) -> Option<f64> {
+        self.stop_price
+    }
+
+    /// Cancels the stop order and returns its final state.
+    pub async fn cancel(&mut self) -> crate::error::Result<Option<Order>> {
+        self.finished = true;
+        let id = match self.order_id.take() {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        match self.client.cancel_order(ORD::OrderID(&id)).text().await {
+            Ok(_) => (),
+            Err(ref error) if matches!(error.status(), Some(400) | Some(404)) => (),
+            Err(error) => return Err(error),
+        }
+        match self.client.get_order(ORD::OrderID(&id)).json::<Order>().await {
+            Ok(order) => Ok(Some(order)),
+            Err(ref error) if error.status() == Some(404) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Follows the price until the stop moves or is done. Returns `None` once done.
+    ///
+    /// If placing a moved stop fails the error is returned and the stop is placed again on the next call.
+    pub async fn next(&mut self) -> crate::error::Result<Option<TrailEvent>> {
+        if self.finished {
+            return Ok(None);
+        }
+        if self.product.is_none() {
+            self.start().await?;
+        }
+
+        loop {
+            if let Some(event) = self.step().await? {
+                if let TrailEvent::Done { .. } = event {
+                    self.finished = true;
+                    if let Some(feed) = self.feed.take() {
+                        feed.close().await.ok();
+                    }
+                }
+                return Ok(Some(event));
+            }
+            self.read().await?;
+        }
+    }
+
+    async fn start(&mut self) -> crate::error::Result<()> {
+        let products: Vec<Product> = self.client.public().get_products().json().await?;
+        let product = products
+            .into_iter()
+            .find(|product| product.id == self.product_id)
+            .ok_or_else(|| Error::new(Kind::Execution, Some(format!("unknown product {}", self.product_id))))?;
+        self.product = Some(product);
+
+        let ticker: Ticker = self.client.public().get_product_ticker(&self.product_id).json().await?;
+        self.observe(ticker.price);
+        Ok(())
+    }
+
+    fn observe(&mut self, price: f64) {
+        self.last_price = Some(price);
+        self.extreme = Some(match (self.extreme, self.side.as_str()) {
+            (Some(extreme), "sell") => extreme.max(price),
+            (Some(extreme), _) => extreme.min(price),
+            (None, _) => price,
+        });
+    }
+
+    /// Returns whether `stop` is closer to the price than the current stop.
+    fn improves(&self, stop: f64) -> bool {
+        match (self.stop_price, self.side.as_str()) {
+            (Some(current), "sell") => stop > current,
+            (Some(current), _) => stop < current,
+            (None, _) => true,
+        }
+    }
+
+    fn target(&self) -> Option<f64> {
+        let extreme = self.extreme?;
+        let distance = match self.trail {
+            Trail::Absolute(value) => value,
+            Trail::Percent(value) => extreme * value / 100.0,
+        };
+        let increment = self.product.as_ref()?.quote_increment;
+        // round away from the price so the trail is never tighter than asked
+        let stop = if self.side == "sell" {
+            ((extreme - distance) / increment + 1e-9).floor() * increment
+        } else {
+            ((extreme + distance) / increment - 1e-9).ceil() * increment
+        };
+        Some(snap(stop, increment))
+    }
+
+    fn limit(&self, stop: f64) -> f64 {
+        let increment = self.product.as_ref().unwrap().quote_increment;
+        let limit = if self.side == "sell" { stop - self.limit_offset } else { stop + self.limit_offset };
+        snap(limit, increment)
+    }
+
+    async fn step(&mut self) -> crate::error::Result<Option<TrailEvent>> {
+        let due = match self.repriced_at {
+            Some(at) => at.elapsed() >= self.min_reprice_interval,
+            None => true,
+        };
+        let target = match self.target() {
+            Some(target) => target,
+            None => return Ok(None),
+        };
+
+        let id = match self.order_id.clone() {
+            Some(id) => id,
+            None => {
+                // first placement or a failed move, the stop is restored at the best of the old and new level
+                let stop = match self.stop_price {
+                    Some(stop) if !self.improves(target) => stop,
+                    _ => target,
+                };
+                self.repriced_at = Some(Instant::now());
+                let order: Order = self
+                    .client
+                    .place_limit_order(&self.product_id, &self.side, self.limit(stop), self.size)
+                    .stop_price(stop)
+                    .json()
+                    .await?;
+                self.order_id = Some(order.id.clone());
+                self.stop_price = Some(stop);
+                return Ok(Some(TrailEvent::Moved { order_id: order.id, stop_price: stop }));
+            }
+        };
+
+        // the stop may have triggered once the price crossed it
+        let crossed = match (self.last_price, self.stop_price, self.side.as_str()) {
+            (Some(price), Some(stop), "sell") => price <= stop,
+            (Some(price), Some(stop), _) => price >= stop,
+            _ => false,
+        };
+        if crossed && due {
+            self.repriced_at = Some(Instant::now());
+            return match self.client.get_order(ORD::OrderID(&id)).json::<Order>().await {
+                Ok(order) if order.status == "done" => Ok(Some(TrailEvent::Done { order: Some(Box::new(order)) })),
+                Ok(_) => Ok(None),
+                Err(ref error) if error.status() == Some(404) => Ok(Some(TrailEvent::Done { order: None })),
+                Err(error) => Err(error),
+            };
+        }
+
+        if crossed || !due || !self.improves(target) {
+            return Ok(None);
+        }
+        self.repriced_at = Some(Instant::now());
+        let request = OrderRequest::limit(self.product_id.clone(), self.side.clone(), self.limit(target), self.size).stop_price(target);
+        let outcome = self.client.replace_order(ORD::OrderID(&id), request).reduce_by_filled(true).send().await?;
+        match outcome {
+            ReplaceOutcome::Replaced { placed, .. } | ReplaceOutcome::PartiallyFilled { placed, .. } => {
+                self.size = placed.size.unwrap_or(self.size);
+                self.order_id = Some(placed.id.clone());
+                self.stop_price = Some(target);
+                Ok(Some(TrailEvent::Moved { order_id: placed.id, stop_price: target }))
+            }
+            ReplaceOutcome::Filled { order } => Ok(Some(TrailEvent::Done { order: Some(Box::new(order)) })),
+            ReplaceOutcome::NotFound => Ok(Some(TrailEvent::Done { order: None })),
+            // the old stop may still rest, try again after the interval
+            ReplaceOutcome::Unconfirmed { .. } => Ok(None),
+            ReplaceOutcome::PlaceFailed { canceled, error } => {
+                // the stop is placed again with what the canceled order had left
+                if let Some(Order { size: Some(size), filled_size, .. }) = canceled {
+                    self.size = size - filled_size;
+                }
+                self.order_id = None;
+                Err(error)
+            }
+        }
+    }
+
+    async fn read(&mut self) -> crate::error::Result<()> {
+        if let Some(mut feed) = self.feed.take() {
+            match tokio::time::timeout(self.tick, feed.json::<serde_json::Value>()).await {
+                Ok(Ok(Some(value))) => {
+                    self.feed = Some(feed);
+                    let message: FeedMessage = serde_json::from_value(value)?;
+                    let price_message = message.type_ == "ticker" || message.type_ == "match" || message.type_ == "last_match";
+                    if let (true, Some(price)) = (price_message, message.price) {
+                        if message.product_id.as_deref() == Some(self.product_id.as_str()) {
+                            self.observe(price);
+                        }
+                    }
+                }
+                Ok(_) => (),
+                Err(_) => self.feed = Some(feed),
+            }
+            return Ok(());
+        }
+
+        let due = match self.connected_at {
+            Some(at) => at.elapsed() >= self.reconnect_interval,
+            None => true,
+        };
+        if due {
+            self.connected_at = Some(Instant::now());
+            self.feed = self.connect().await.ok();
+        }
+        if self.feed.is_none() {
+            let ticker: Ticker = self.client.public().get_product_ticker(&self.product_id).json().await?;
+            self.observe(ticker.price);
+            tokio::time::delay_for(self.tick).await;
+        }
+        Ok(())
+    }
+
+    async fn connect(&self) -> crate::error::Result<WebSocketFeed> {
+        let mut feed = WebSocketFeed::connect(self.url.clone()).await?;
+        feed.subscribe(&[&self.product_id], &[self.channel]).await?;
+        Ok(feed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::mock::{self, Received, Reply, Requests};
+
+    fn product() -> Product {
+        serde_json::from_value(json!({
+            "id": "BTC-USD",
+            "base_currency": "BTC",
+            "quote_currency": "USD",
+            "base_increment": "0.001",
+            "quote_increment": "0.01",
+        }))
+        .unwrap()
+    }
+
+    fn order(id: &str, size: f64, filled_size: f64, status: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "product_id": "BTC-USD",
+            "side": "sell",
+            "type": "limit",
+            "size": size.to_string(),
+            "status": status,
+            "done_reason": if status == "done" { Some("canceled") } else { None },
+            "filled_size": filled_size.to_string(),
+            "created_at": "2020-01-01T00:00:00Z",
+        })
+    }
+
+    fn body(request: &Received) -> (f64, f64) {
+        let body = request.json();
+        (body["size"].as_f64().unwrap(), body["stop_price"].as_f64().unwrap())
+    }
+
+    /// Sell stop of 1.0 resting at 90 as `old` with the price at 110, so a trail of 10 moves it to 100.
+    fn resting_stop(purged: bool) -> (TrailingStop, Requests) {
+        let mut posts = 0;
+        let (url, requests) = mock::serve(move |request| match (request.method.as_str(), request.url.path()) {
+            ("DELETE", "/orders/old") => Reply::ok(json!(["old"])),
+            ("GET", "/orders/old") if purged => Reply::status(404, json!({"message": "NotFound"})),
+            ("GET", "/orders/old") => Reply::ok(order("old", 1.0, 0.4, "done")),
+            ("POST", "/orders") => {
+                posts += 1;
+                if posts == 1 {
+                    Reply::status(400, json!({"message": "Insufficient funds"}))
+                } else {
+                    Reply::ok(order("new", body(request).0, 0.0, "pending"))
+                }
+            }
+            _ => Reply::status(404, json!({"message": "NotFound"})),
+        });
+
+        let client = AuthenticatedClient::new("key", "pass", "c2VjcmV0", url.as_str());
+        let mut stop = TrailingStop::new(client, "ws://127.0.0.1:9", "BTC-USD", "sell", 1.0, Trail::Absolute(10.0))
+            .min_reprice_interval(Duration::from_secs(0));
+        stop.product = Some(product());
+        stop.order_id = Some("old".to_string());
+        stop.stop_price = Some(90.0);
+        stop.observe(110.0);
+        (stop, requests)
+    }
+
+    fn placed(requests: &Requests) -> Vec<(f64, f64)> {
+        requests.lock().unwrap().iter().filter(|request| request.method == "POST").map(body).collect()
+    }
+
+    #[test]
+    fn target_trails_extreme_rounded_away_from_price() {
+        let client = AuthenticatedClient::new("key", "pass", "c2VjcmV0", "http://127.0.0.1:9");
+        let mut stop = TrailingStop::new(client.clone(), "ws://127.0.0.1:9", "BTC-USD", "sell", 1.0, Trail::Percent(2.0));
+        stop.product = Some(product());
+        stop.observe(100.555);
+        stop.observe(99.0);
+        assert_eq!(stop.target(), Some(98.54));
+        assert!(stop.improves(98.54));
+        stop.stop_price = Some(98.54);
+        assert!(!stop.improves(98.0));
+
+        let mut stop = TrailingStop::new(client, "ws://127.0.0.1:9", "BTC-USD", "buy", 1.0, Trail::Absolute(1.0)).limit_offset(0.5);
+        stop.product = Some(product());
+        stop.observe(100.001);
+        stop.observe(102.0);
+        assert_eq!(stop.target(), Some(101.01));
+        assert_eq!(stop.limit(101.01), 101.51);
+    }
+
+    #[tokio::test]
+    async fn failed_move_is_placed_again_with_remaining_size() {
+        let (mut stop, requests) = resting_stop(false);
+
+        assert!(stop.step().await.is_err());
+        assert_eq!(stop.order_id(), None);
+        assert_eq!(stop.stop_price(), Some(90.0));
+
+        match stop.step().await.unwrap() {
+            Some(TrailEvent::Moved { order_id, stop_price }) => assert_eq!((order_id.as_str(), stop_price), ("new", 100.0)),
+            event => panic!("unexpected {:?}", event),
+        }
+        assert_eq!(placed(&requests), vec![(0.6, 100.0), (0.6, 100.0)]);
+        assert_eq!(stop.order_id(), Some("new"));
+    }
+
+    #[tokio::test]
+    async fn failed_move_of_purged_stop_keeps_size() {
+        let (mut stop, requests) = resting_stop(true);
+
+        assert!(stop.step().await.is_err());
+        stop.step().await.unwrap();
+        assert_eq!(placed(&requests), vec![(1.0, 100.0), (1.0, 100.0)]);
+    }
+}
@@ -2,7 +2,7 @@ use core::pin::Pin;
 use futures::{
     SinkExt,
     sink::Sink,
-    stream::{Stream, TryStreamExt},
+    stream::{Stream, StreamExt, TryStreamExt},
     task::{Context, Poll},
 };
 use async_tungstenite::{
@@ -24,13 +24,15 @@ use async_tungstenite::{
 
 use tokio::net::TcpStream;
 use tokio_tls::TlsStream;
-use serde::Serialize;
-use std::collections::HashMap;
-use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
 use hmac::{ Hmac, Mac };
 use sha2::Sha256;
 use crate::client::Auth;
-use crate::error::{Error, Kind, WsCloseError};
+use crate::error::{Error, FeedError, Kind, WsCloseError};
 use serde::de::DeserializeOwned;
 
 /// wss://ws-feed-public.sandbox.pro.coinbase.com
@@ -49,26 +51,179 @@ impl Channels {
     pub const USER: &'static str = "user";
     pub const MATCHES: &'static str = "matches";
     pub const FULL: &'static str = "full";
+    pub const LEVEL2_BATCH: &'static str = "level2_batch";
+    pub const TICKER_BATCH: &'static str = "ticker_batch";
+    pub const AUCTION: &'static str = "auctionfeed";
+}
+
+/// Channel of a subscribe message
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ChannelSpec<'a> {
+    /// Channel for the product ids of the message
+    Name(&'a str),
+    /// Channel with its own product ids, e.g. `{"name": "ticker", "product_ids": ["BTC-USD"]}`
+    Products { name: &'a str, product_ids: &'a [&'a str] },
+}
+
+impl<'a> From<&'a str> for ChannelSpec<'a> {
+    fn from(name: &'a str) -> Self {
+        ChannelSpec::Name(name)
+    }
+}
+
+/// Channel and its products as confirmed by a `subscriptions` message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub name: String,
+    #[serde(default)]
+    pub product_ids: Vec<String>,
 }
 
 #[derive(Serialize)]
 struct SubscribeMessage<'a> {
     #[serde(rename(serialize = "type"))]
     type_: &'a str,
+    #[serde(skip_serializing_if = "<[&str]>::is_empty")]
     product_ids: &'a [&'a str],
-    channels: &'a [&'a str],
+    channels: &'a [ChannelSpec<'a>],
 
     #[serde(flatten)]
     auth: Option<HashMap<&'a str, String>>,
 }
 
+/// Type of a feed message, the rest of the message is skipped
+#[derive(Deserialize)]
+struct TypedMessage<'a> {
+    #[serde(rename = "type", borrow)]
+    type_: Cow<'a, str>,
+}
+
+fn message_type(text: &str) -> Option<Cow<'_, str>> {
+    serde_json::from_str::<TypedMessage>(text).ok().map(|message| message.type_)
+}
+
+/// Text of text messages and of binary messages holding UTF-8
+fn message_text(msg: &Message) -> Option<&str> {
+    match msg {
+        Message::Text(text) => Some(text),
+        Message::Binary(data) => std::str::from_utf8(data).ok(),
+        _ => None,
+    }
+}
+
+/// `subscriptions` and `error` messages of the feed
+#[derive(Deserialize)]
+struct ControlMessage {
+    #[serde(rename = "type")]
+    type_: String,
+    #[serde(default)]
+    channels: Vec<Subscription>,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HeartbeatMessage {
+    product_id: String,
+    #[serde(default)]
+    sequence: Option<u64>,
+    #[serde(default)]
+    last_trade_id: Option<u64>,
+    #[serde(default)]
+    time: Option<DateTime<Utc>>,
+}
+
+/// Last `heartbeat` message of a product
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    pub received_at: Instant,
+    pub time: Option<DateTime<Utc>>,
+    pub sequence: Option<u64>,
+    pub last_trade_id: Option<u64>,
+}
+
+/// Message or liveness event returned by [next_event](struct.WebSocketFeed.html#method.next_event)
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedEvent {
+    /// Text message of the feed, binary messages holding UTF-8 included
+    Text(String),
+    /// No heartbeat arrived within the window set with `stale_after` for these products
+    Stale { product_ids: Vec<String> },
+    /// A ping sent on idle was not answered within the pong timeout
+    PongMissing,
+    /// Ping frame received, already answered with a pong. Only with `control_events`
+    Ping(Vec<u8>),
+    /// Pong frame received. Only with `control_events`
+    Pong(Vec<u8>),
+    /// Binary message that is not UTF-8. Only with `control_events`, skipped otherwise
+    Binary(Vec<u8>),
+    /// Close frame received, the feed ends after it. Only with `control_events`, an error otherwise
+    Close { code: CloseCode, reason: String },
+}
+
+struct Liveness {
+    auto_heartbeat: bool,
+    stale_after: Option<Duration>,
+    idle_ping: Option<Duration>,
+    pong_timeout: Option<Duration>,
+    errors: bool,
+    heartbeats: HashMap<String, Heartbeat>,
+    // products expected to send heartbeats, with the time of the last one and whether they were reported stale
+    watched: HashMap<String, (Instant, bool)>,
+    last_received: Instant,
+    ping_sent: Option<Instant>,
+    pong_missing: bool,
+    control_events: bool,
+}
+
+impl Liveness {
+    fn new() -> Self {
+        Self {
+            auto_heartbeat: false,
+            stale_after: None,
+            idle_ping: None,
+            pong_timeout: None,
+            errors: false,
+            heartbeats: HashMap::new(),
+            watched: HashMap::new(),
+            last_received: Instant::now(),
+            ping_sent: None,
+            pong_missing: false,
+            control_events: false,
+        }
+    }
+
+    /// Earliest time a liveness check is due.
+    fn deadline(&self) -> Option<Instant> {
+        let mut deadlines = Vec::new();
+        match (self.ping_sent, self.idle_ping, self.pong_timeout) {
+            (None, Some(idle), _) => deadlines.push(self.last_received + idle),
+            (Some(sent), _, Some(timeout)) if !self.pong_missing => deadlines.push(sent + timeout),
+            _ => (),
+        }
+        if let Some(window) = self.stale_after {
+            deadlines.extend(self.watched.values().filter(|(_, stale)| !stale).map(|(at, _)| *at + window));
+        }
+        deadlines.into_iter().min()
+    }
+}
+
 type HmacSha256 = Hmac<Sha256>;
 
 /// Stream with private or public access to Coinbase's Websocket Feed
 pub struct WebSocketFeed {
     inner: WebSocketStream<StreamSwitcher<TokioAdapter<TcpStream>, TokioAdapter<TlsStream<TokioAdapter<TokioAdapter<TcpStream>>>>>>,
     response: Response,
-    auth: Option<Auth>
+    auth: Option<Auth>,
+    buffered: VecDeque<Message>,
+    subscriptions: Vec<Subscription>,
+    // subscribe and unsubscribe messages not yet answered by `subscriptions` or `error`
+    unconfirmed: usize,
+    events: VecDeque<FeedEvent>,
+    liveness: Liveness,
 }
 
 impl WebSocketFeed {
@@ -96,7 +251,12 @@ impl WebSocketFeed {
         Ok(WebSocketFeed {
             inner: ws_stream,
             response: res,
-            auth: None
+            auth: None,
+            buffered: VecDeque::new(),
+            subscriptions: Vec::new(),
+            unconfirmed: 0,
+            events: VecDeque::new(),
+            liveness: Liveness::new(),
         })
         
     }
@@ -135,37 +295,247 @@ impl WebSocketFeed {
                 pass: pass.into(), 
                 secret: secret.into() 
             }
-        )
+        ),
+            buffered: VecDeque::new(),
+            subscriptions: Vec::new(),
+            unconfirmed: 0,
+            events: VecDeque::new(),
+            liveness: Liveness::new(),
         })
         
     }
 
+    /// Sends `heartbeat` subscriptions along with every subscribe, the heartbeat messages are consumed
+    /// and only tracked in `heartbeats`.
+    pub fn auto_heartbeat(mut self, value: bool) -> Self {
+        self.liveness.auto_heartbeat = value;
+        self
+    }
+
+    /// Reports products subscribed to `heartbeat` as stale when no heartbeat arrives within `window`.
+    pub fn stale_after(mut self, window: Duration) -> Self {
+        self.liveness.stale_after = Some(window);
+        self
+    }
+
+    /// Sends a websocket ping when nothing was received for `idle` and reports a missing pong after `timeout`.
+    pub fn idle_ping(mut self, idle: Duration, timeout: Duration) -> Self {
+        self.liveness.idle_ping = Some(idle);
+        self.liveness.pong_timeout = Some(timeout);
+        self
+    }
+
+    /// Makes `text` and `json` fail on stale heartbeats and missing pongs, by default they are only
+    /// reported by `next_event`.
+    pub fn liveness_errors(mut self, value: bool) -> Self {
+        self.liveness.errors = value;
+        self
+    }
+
+    /// Returns ping, pong, non UTF-8 binary and close frames from `next_event`. Pings are answered either way.
+    pub fn control_events(mut self, value: bool) -> Self {
+        self.liveness.control_events = value;
+        self
+    }
+
+    /// Returns the last heartbeat of every product.
+    pub fn heartbeats(&self) -> &HashMap<String, Heartbeat> {
+        &self.liveness.heartbeats
+    }
+
+    /// Returns the last heartbeat of a product.
+    pub fn heartbeat(&self, product_id: &str) -> Option<&Heartbeat> {
+        self.liveness.heartbeats.get(product_id)
+    }
+
+    /// Waits for the next text message or liveness event.
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cbpro::websocket::{Channels, FeedEvent, WebSocketFeed, SANDBOX_FEED_URL};
+    /// use core::time::Duration;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut feed = WebSocketFeed::connect(SANDBOX_FEED_URL)
+    ///     .await?
+    ///     .auto_heartbeat(true)
+    ///     .stale_after(Duration::from_secs(5))
+    ///     .idle_ping(Duration::from_secs(10), Duration::from_secs(5));
+    /// feed.subscribe(&["BTC-USD"], &[Channels::TICKER]).await?;
+    ///
+    /// while let Some(event) = feed.next_event().await? {
+    ///     match event {
+    ///         FeedEvent::Text(text) => println!("{}", text),
+    ///         FeedEvent::Stale { product_ids } => println!("no heartbeat for {:?}", product_ids),
+    ///         FeedEvent::PongMissing => break,
+    ///         _ => (),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn next_event(&mut self) -> crate::error::Result<Option<FeedEvent>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+            // buffered while confirming a subscription, already inspected
+            if let Some(msg) = self.buffered.pop_front() {
+                self.handle(msg, true).await?;
+                continue;
+            }
+
+            let msg = match self.liveness.deadline() {
+                Some(deadline) if deadline <= Instant::now() => {
+                    self.check_liveness().await?;
+                    continue;
+                }
+                Some(deadline) => {
+                    let deadline = tokio::time::Instant::from_std(deadline);
+                    match tokio::time::timeout_at(deadline, self.try_next()).await {
+                        Ok(msg) => msg?,
+                        Err(_) => continue,
+                    }
+                }
+                None => self.try_next().await?,
+            };
+            let msg = match msg {
+                Some(msg) => msg,
+                None => return Ok(None),
+            };
+
+            self.liveness.last_received = Instant::now();
+            self.handle(msg, false).await?;
+        }
+    }
+
     pub async fn text(&mut self) -> crate::error::Result<Option<String>> {
-        match self.try_next().await? {
-            Some(msg) => {
-                match msg {
-                    Message::Text(text) => Ok(Some(text)),
-                    Message::Ping(ref value) => {
-                        self.send(Message::Pong(value.clone())).await?;
-                        let ping = serde_json::json!({
-                            "ping": msg.into_text()?,
-                        });
-                        Ok(Some(serde_json::to_string(&ping)?))
-                    },
-                    Message::Pong(ref value) => {
-                        self.send(Message::Ping(value.clone())).await?;
-                        let pong = serde_json::json!({
-                            "pong": msg.into_text()?,
-                        });
-                        Ok(Some(serde_json::to_string(&pong)?))
-                    },
-                    Message::Binary(_) => Ok(Some(msg.into_text()?)),
-                    Message::Close(Some(frame)) => Err(WsCloseError::new(frame.code, frame.reason).into()),
-                    Message::Close(None) => Err(WsCloseError::new(CloseCode::Abnormal, "Close message with no frame received").into()),
+        loop {
+            match self.next_event().await? {
+                Some(FeedEvent::Text(text)) => return Ok(Some(text)),
+                Some(FeedEvent::Stale { product_ids }) if self.liveness.errors => {
+                    let message = format!("No heartbeat for {} within {:?}", product_ids.join(", "), self.liveness.stale_after.unwrap());
+                    return Err(Error::new(Kind::Liveness, Some(message)));
                 }
+                Some(FeedEvent::PongMissing) if self.liveness.errors => {
+                    return Err(Error::new(Kind::Liveness, Some("No pong received for an idle ping")));
+                }
+                Some(FeedEvent::Close { code, reason }) => return Err(WsCloseError::new(code, reason).into()),
+                Some(_) => (),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Answers pings and queues the events of a message.
+    async fn handle(&mut self, msg: Message, inspected: bool) -> crate::error::Result<()> {
+        let control_events = self.liveness.control_events;
+        match msg {
+            Message::Text(text) => self.handle_text(text, inspected),
+            Message::Binary(data) => match String::from_utf8(data) {
+                Ok(text) => self.handle_text(text, inspected),
+                Err(error) if control_events => self.events.push_back(FeedEvent::Binary(error.into_bytes())),
+                Err(_) => (),
             },
-            None => Ok(None)
+            Message::Ping(data) => {
+                // tungstenite queues the pong itself
+                self.flush().await?;
+                if control_events {
+                    self.events.push_back(FeedEvent::Ping(data));
+                }
+            }
+            Message::Pong(data) => {
+                self.liveness.ping_sent = None;
+                self.liveness.pong_missing = false;
+                if control_events {
+                    self.events.push_back(FeedEvent::Pong(data));
+                }
+            }
+            Message::Close(frame) => {
+                let (code, reason) = match frame {
+                    Some(frame) => (frame.code, frame.reason.into_owned()),
+                    None => (CloseCode::Abnormal, "Close message with no frame received".to_string()),
+                };
+                if !control_events {
+                    return Err(WsCloseError::new(code, reason).into());
+                }
+                self.events.push_back(FeedEvent::Close { code, reason });
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_text(&mut self, text: String, inspected: bool) {
+        if !self.observe(&text, inspected) {
+            self.events.push_back(FeedEvent::Text(text));
+        }
+    }
+
+    /// Tracks subscriptions and heartbeats of a text message, returns whether the message is consumed.
+    fn observe(&mut self, text: &str, inspected: bool) -> bool {
+        match message_type(text).as_deref() {
+            Some("heartbeat") => self.track_heartbeat(text),
+            Some(type_) if !inspected => {
+                self.inspect(type_, text).ok();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Records heartbeat messages, returns whether the message is consumed.
+    fn track_heartbeat(&mut self, text: &str) -> bool {
+        let message: HeartbeatMessage = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(_) => return false,
+        };
+        let now = Instant::now();
+        if let Some(watched) = self.liveness.watched.get_mut(&message.product_id) {
+            *watched = (now, false);
         }
+        self.liveness.heartbeats.insert(message.product_id, Heartbeat {
+            received_at: now,
+            time: message.time,
+            sequence: message.sequence,
+            last_trade_id: message.last_trade_id,
+        });
+        self.liveness.auto_heartbeat
+    }
+
+    /// Queues stale and missing pong events and sends a ping when idle.
+    async fn check_liveness(&mut self) -> crate::error::Result<()> {
+        let now = Instant::now();
+        let liveness = &mut self.liveness;
+
+        if let Some(window) = liveness.stale_after {
+            let mut product_ids: Vec<String> = liveness
+                .watched
+                .iter_mut()
+                .filter(|(_, (at, stale))| !*stale && now.duration_since(*at) >= window)
+                .map(|(product_id, watched)| {
+                    watched.1 = true;
+                    product_id.clone()
+                })
+                .collect();
+            if !product_ids.is_empty() {
+                product_ids.sort();
+                self.events.push_back(FeedEvent::Stale { product_ids });
+            }
+        }
+
+        match (liveness.ping_sent, liveness.idle_ping, liveness.pong_timeout) {
+            (Some(sent), _, Some(timeout)) if !liveness.pong_missing && now.duration_since(sent) >= timeout => {
+                liveness.pong_missing = true;
+                self.events.push_back(FeedEvent::PongMissing);
+            }
+            (None, Some(idle), _) if now.duration_since(liveness.last_received) >= idle => {
+                liveness.ping_sent = Some(now);
+                self.send(Message::Ping(Utc::now().timestamp_millis().to_string().into_bytes())).await?;
+            }
+            _ => (),
+        }
+        Ok(())
     }
 
     pub async fn json<J: DeserializeOwned>(&mut self) -> crate::error::Result<Option<J>> {
@@ -177,8 +547,56 @@ impl WebSocketFeed {
 
     /// Subscribe to a list of channels and products.
     pub async fn subscribe(&mut self, product_ids: &[&str], channels: &[&str]) -> crate::error::Result<()> {
-        let auth = match self.auth {
-            Some(ref auth) => {
+        let channels: Vec<ChannelSpec> = channels.iter().map(|&name| ChannelSpec::Name(name)).collect();
+        self.send_subscription("subscribe", product_ids, &channels).await
+    }
+
+    /// Unsubscribe to a list of channels and products.
+    pub async fn unsubscribe(&mut self, product_ids: &[&str], channels: &[&str]) -> crate::error::Result<()> {
+        let channels: Vec<ChannelSpec> = channels.iter().map(|&name| ChannelSpec::Name(name)).collect();
+        self.send_subscription("unsubscribe", product_ids, &channels).await
+    }
+
+    /// Subscribes and waits for the `subscriptions` confirmation, an `error` message fails the subscription.
+    /// Other messages received in the meantime are kept for `text`, `json` and the stream.
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cbpro::websocket::{ChannelSpec, Channels, WebSocketFeed, SANDBOX_FEED_URL};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut feed = WebSocketFeed::connect(SANDBOX_FEED_URL).await?;
+    /// let subscriptions = feed
+    ///     .subscribe_channels(&["BTC-USD"], &[
+    ///         ChannelSpec::Name(Channels::HEARTBEAT),
+    ///         ChannelSpec::Products { name: Channels::TICKER, product_ids: &["ETH-USD", "ETH-EUR"] },
+    ///     ])
+    ///     .await?;
+    ///
+    /// println!("{:?}", subscriptions);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn subscribe_channels(&mut self, product_ids: &[&str], channels: &[ChannelSpec<'_>]) -> crate::error::Result<&[Subscription]> {
+        self.send_subscription("subscribe", product_ids, channels).await?;
+        self.confirm().await
+    }
+
+    /// Unsubscribes and waits for the `subscriptions` confirmation.
+    pub async fn unsubscribe_channels(&mut self, product_ids: &[&str], channels: &[ChannelSpec<'_>]) -> crate::error::Result<&[Subscription]> {
+        self.send_subscription("unsubscribe", product_ids, channels).await?;
+        self.confirm().await
+    }
+
+    /// Returns the channels of the last `subscriptions` message received.
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    async fn send_subscription(&mut self, type_: &str, product_ids: &[&str], channels: &[ChannelSpec<'_>]) -> crate::error::Result<()> {
+        let auth = match (type_, &self.auth) {
+            ("subscribe", Some(auth)) => {
                 let timestamp = Utc::now().timestamp().to_string();
                 let message = timestamp.clone() + "GET" + "/users/self/verify";
         
@@ -196,21 +614,83 @@ impl WebSocketFeed {
                 map.insert("signature", b64_signature);
                 Some(map)
             },
-            None => None
+            _ => None
         };
-        let message = SubscribeMessage {type_: "subscribe", product_ids, channels, auth};
+        // heartbeat for every product of the message, the ones of per channel product lists included
+        let mut heartbeat_ids = product_ids.to_vec();
+        for channel in channels {
+            if let ChannelSpec::Products { product_ids, .. } = channel {
+                for product_id in product_ids.iter() {
+                    if !heartbeat_ids.contains(product_id) {
+                        heartbeat_ids.push(product_id);
+                    }
+                }
+            }
+        }
+        let has_heartbeat = channels.iter().any(|channel| match channel {
+            ChannelSpec::Name(name) | ChannelSpec::Products { name, .. } => *name == Channels::HEARTBEAT,
+        });
+        let mut channels = channels.to_vec();
+        if type_ == "subscribe" && self.liveness.auto_heartbeat && !has_heartbeat && !heartbeat_ids.is_empty() {
+            channels.push(ChannelSpec::Products { name: Channels::HEARTBEAT, product_ids: &heartbeat_ids });
+        }
+        let message = SubscribeMessage {type_, product_ids, channels: &channels, auth};
         let message = serde_json::to_string(&message).unwrap();
         self.send(Message::Text(message)).await?;
+        self.unconfirmed += 1;
 
         Ok(())
     }
 
-    /// Unsubscribe to a list of channels and products.
-    pub async fn unsubscribe(&mut self, product_ids: &[&str], channels: &[&str]) -> crate::error::Result<()> {
-        let message = SubscribeMessage {type_: "unsubscribe", product_ids, channels, auth: None};
-        let message = serde_json::to_string(&message).unwrap();
-        self.send(Message::Text(message)).await?;
-        Ok(())
+    /// Reads until the `subscriptions` or `error` message answering the last request and buffers everything else,
+    /// answers to earlier requests included.
+    async fn confirm(&mut self) -> crate::error::Result<&[Subscription]> {
+        loop {
+            let msg = match self.inner.next().await {
+                Some(msg) => msg?,
+                None => return Err(WsCloseError::new(CloseCode::Abnormal, "Feed closed before the subscription was confirmed").into()),
+            };
+            if let Some(text) = message_text(&msg) {
+                if let Some(type_) = message_type(text) {
+                    let result = self.inspect(&type_, text);
+                    if !matches!(result, Ok(None)) && self.unconfirmed == 0 {
+                        return result.map(move |_| &self.subscriptions[..]);
+                    }
+                }
+            }
+            self.buffered.push_back(msg);
+        }
+    }
+
+    /// Tracks `subscriptions` messages, returns `Some(true)` for them and an error for `error` messages.
+    fn inspect(&mut self, type_: &str, text: &str) -> crate::error::Result<Option<bool>> {
+        if type_ != "subscriptions" && type_ != "error" {
+            return Ok(None);
+        }
+        let message: ControlMessage = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(_) => return Ok(None),
+        };
+        match message.type_.as_str() {
+            "subscriptions" => {
+                let now = Instant::now();
+                let watched = &mut self.liveness.watched;
+                let heartbeat = message.channels.iter().find(|channel| channel.name == Channels::HEARTBEAT);
+                let product_ids = heartbeat.map(|channel| channel.product_ids.clone()).unwrap_or_default();
+                watched.retain(|product_id, _| product_ids.contains(product_id));
+                for product_id in product_ids {
+                    watched.entry(product_id).or_insert((now, false));
+                }
+                self.subscriptions = message.channels;
+                self.unconfirmed = self.unconfirmed.saturating_sub(1);
+                Ok(Some(true))
+            }
+            "error" => {
+                self.unconfirmed = self.unconfirmed.saturating_sub(1);
+                Err(FeedError::new(message.message.unwrap_or_default(), message.reason).into())
+            }
+            _ => Ok(None),
+        }
     }
 
     /// Returns a shared reference to the inner stream.
@@ -239,11 +719,25 @@ impl WebSocketFeed {
 impl Stream for WebSocketFeed {
     type Item = crate::error::Result<Message>;
 
+    /// Raw messages, subscriptions and heartbeats are tracked the same way as for `next_event`.
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        match Pin::new(&mut self.inner).poll_next(cx) {
-            Poll::Ready(Some(val)) => Poll::Ready(Some(Ok(val?))),
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
+        loop {
+            // buffered while confirming a subscription, already inspected
+            let (msg, inspected) = match self.buffered.pop_front() {
+                Some(msg) => (msg, true),
+                None => match Pin::new(&mut self.inner).poll_next(cx) {
+                    Poll::Ready(Some(val)) => (val?, false),
+                    Poll::Ready(None) => return Poll::Ready(None),
+                    Poll::Pending => return Poll::Pending,
+                },
+            };
+            let consumed = match message_text(&msg) {
+                Some(text) => self.observe(text, inspected),
+                None => false,
+            };
+            if !consumed {
+                return Poll::Ready(Some(Ok(msg)));
+            }
         }
     }
 }
@@ -0,0 +1,100 @@
+use cbpro::models::LedgerEntry;
+use cbpro::tax::{tax_lots, write_csv, AccountLedger, TaxLot, FIAT};
+
+fn ledger(currency: &str) -> AccountLedger {
+    let path = format!("{}/tests/fixtures/ledgers/{}.json", env!("CARGO_MANIFEST_DIR"), currency);
+    let entries: Vec<LedgerEntry> = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    AccountLedger {
+        account_id: format!("{}-account", currency),
+        currency: currency.to_string(),
+        entries,
+    }
+}
+
+fn lots() -> Vec<TaxLot> {
+    let ledgers: Vec<AccountLedger> = ["USD", "BTC", "USDC", "ETH"].iter().map(|currency| ledger(currency)).collect();
+    tax_lots(&ledgers, FIAT)
+}
+
+#[test]
+fn sale_closes_oldest_lot_with_fees() {
+    let lots = lots();
+    let sale = &lots[0];
+
+    assert_eq!(sale.currency, "BTC");
+    assert_eq!(sale.size, 1.0);
+    assert_eq!(sale.acquired_via.as_deref(), Some("match"));
+    assert_eq!(sale.acquired_ref.as_deref(), Some("1"));
+    assert_eq!(sale.cost_basis, Some(5025.0));
+    assert_eq!(sale.cost_currency.as_deref(), Some("USD"));
+    assert_eq!(sale.disposed_ref.as_deref(), Some("3"));
+    assert_eq!(sale.proceeds, Some(6965.0));
+    assert_eq!(sale.gain, Some(1940.0));
+}
+
+#[test]
+fn withdrawal_splits_lot_without_proceeds() {
+    let lots = lots();
+    let withdrawal = &lots[1];
+
+    assert_eq!(withdrawal.currency, "BTC");
+    assert_eq!(withdrawal.size, 0.25);
+    assert_eq!(withdrawal.acquired_ref.as_deref(), Some("2"));
+    assert_eq!(withdrawal.cost_basis, Some(1507.5));
+    assert_eq!(withdrawal.disposed_via.as_deref(), Some("transfer"));
+    assert_eq!(withdrawal.proceeds, None);
+    assert_eq!(withdrawal.gain, None);
+
+    let remainder = &lots[2];
+    assert_eq!(remainder.currency, "BTC");
+    assert_eq!(remainder.size, 0.25);
+    assert_eq!(remainder.cost_basis, Some(1507.5));
+    assert_eq!(remainder.disposed_at, None);
+}
+
+#[test]
+fn deposits_and_conversions_open_lots() {
+    let lots = lots();
+    assert_eq!(lots.len(), 5);
+
+    let deposit = &lots[3];
+    assert_eq!(deposit.currency, "ETH");
+    assert_eq!(deposit.size, 2.0);
+    assert_eq!(deposit.acquired_via.as_deref(), Some("transfer"));
+    assert_eq!(deposit.cost_basis, None);
+
+    let conversion = &lots[4];
+    assert_eq!(conversion.currency, "USDC");
+    assert_eq!(conversion.size, 1000.0);
+    assert_eq!(conversion.acquired_via.as_deref(), Some("conversion"));
+    assert_eq!(conversion.cost_basis, Some(1000.0));
+    assert_eq!(conversion.cost_currency.as_deref(), Some("USD"));
+}
+
+#[test]
+fn disposal_without_history_has_unknown_lot() {
+    let btc = ledger("BTC");
+    let entries = btc.entries.into_iter().filter(|entry| entry.id == "204").collect();
+    let ledgers = vec![AccountLedger { entries, ..ledger("BTC") }];
+
+    let lots = tax_lots(&ledgers, FIAT);
+    assert_eq!(lots.len(), 1);
+    assert_eq!(lots[0].size, 0.25);
+    assert_eq!(lots[0].acquired_at, None);
+    assert_eq!(lots[0].cost_basis, None);
+}
+
+#[test]
+fn csv_has_header_and_row_per_lot() {
+    let mut buffer = Vec::new();
+    write_csv(&lots(), &mut buffer).unwrap();
+    let csv = String::from_utf8(buffer).unwrap();
+    let mut rows = csv.lines();
+
+    assert_eq!(
+        rows.next(),
+        Some("currency,size,acquired_at,acquired_via,acquired_ref,cost_basis,cost_currency,disposed_at,disposed_via,disposed_ref,proceeds,proceeds_currency,gain")
+    );
+    assert_eq!(rows.next(), Some("BTC,1.0,2019-02-01T10:00:00Z,match,1,5025.0,USD,2019-05-01T09:30:00Z,match,3,6965.0,USD,1940.0"));
+    assert_eq!(rows.count(), 4);
+}
@@ -0,0 +1,171 @@
+use async_tungstenite::tokio::TokioAdapter;
+use async_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use async_tungstenite::tungstenite::protocol::CloseFrame;
+use async_tungstenite::tungstenite::Message;
+use cbpro::websocket::{ChannelSpec, FeedEvent, WebSocketFeed};
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Serves one connection that sends `messages` and returns everything received until the connection ends.
+async fn serve(messages: Vec<Message>) -> (String, JoinHandle<Vec<Message>>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut listener = TcpListener::from_std(listener).unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = async_tungstenite::accept_async(TokioAdapter(socket)).await.unwrap();
+        for message in messages {
+            ws.send(message).await.unwrap();
+        }
+        let mut received = Vec::new();
+        while let Some(Ok(message)) = ws.next().await {
+            received.push(message);
+        }
+        received
+    });
+    (url, server)
+}
+
+fn close(code: CloseCode, reason: &str) -> Message {
+    Message::Close(Some(CloseFrame { code, reason: reason.to_string().into() }))
+}
+
+#[tokio::test]
+async fn ping_is_answered_without_a_text_message() {
+    let (url, server) = serve(vec![Message::Ping(b"hi".to_vec()), Message::Text("{\"type\":\"ticker\"}".into())]).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap();
+
+    assert_eq!(feed.text().await.unwrap().as_deref(), Some("{\"type\":\"ticker\"}"));
+    feed.close().await.unwrap();
+
+    let received = server.await.unwrap();
+    assert_eq!(received[0], Message::Pong(b"hi".to_vec()));
+    assert!(matches!(received[1], Message::Close(_)));
+}
+
+#[tokio::test]
+async fn pong_is_not_answered() {
+    let (url, server) = serve(vec![Message::Pong(b"hi".to_vec()), Message::Text("{\"type\":\"ticker\"}".into())]).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap();
+
+    assert_eq!(feed.text().await.unwrap().as_deref(), Some("{\"type\":\"ticker\"}"));
+    feed.close().await.unwrap();
+
+    let received = server.await.unwrap();
+    assert_eq!(received.len(), 1);
+    assert!(matches!(received[0], Message::Close(_)));
+}
+
+#[tokio::test]
+async fn control_frames_as_events() {
+    let (url, server) = serve(vec![
+        Message::Ping(b"ping".to_vec()),
+        Message::Pong(b"pong".to_vec()),
+        Message::Text("{\"type\":\"ticker\"}".into()),
+    ])
+    .await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap().control_events(true);
+
+    assert_eq!(feed.next_event().await.unwrap(), Some(FeedEvent::Ping(b"ping".to_vec())));
+    assert_eq!(feed.next_event().await.unwrap(), Some(FeedEvent::Pong(b"pong".to_vec())));
+    assert_eq!(feed.next_event().await.unwrap(), Some(FeedEvent::Text("{\"type\":\"ticker\"}".into())));
+    feed.close().await.unwrap();
+
+    assert_eq!(server.await.unwrap()[0], Message::Pong(b"ping".to_vec()));
+}
+
+#[tokio::test]
+async fn binary_messages() {
+    let binary = || vec![
+        Message::Binary(vec![0xff, 0xfe, 0x00]),
+        Message::Binary(b"{\"type\":\"ticker\"}".to_vec()),
+    ];
+
+    let (url, _) = serve(binary()).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap();
+    assert_eq!(feed.text().await.unwrap().as_deref(), Some("{\"type\":\"ticker\"}"));
+
+    let (url, _) = serve(binary()).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap().control_events(true);
+    assert_eq!(feed.next_event().await.unwrap(), Some(FeedEvent::Binary(vec![0xff, 0xfe, 0x00])));
+    assert_eq!(feed.next_event().await.unwrap(), Some(FeedEvent::Text("{\"type\":\"ticker\"}".into())));
+}
+
+#[tokio::test]
+async fn close_frame() {
+    let (url, _) = serve(vec![close(CloseCode::Away, "restarting")]).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap();
+    let error = feed.text().await.unwrap_err();
+    assert!(error.to_string().ends_with("restarting"));
+
+    let (url, _) = serve(vec![close(CloseCode::Away, "restarting")]).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap().control_events(true);
+    assert_eq!(
+        feed.next_event().await.unwrap(),
+        Some(FeedEvent::Close { code: CloseCode::Away, reason: "restarting".into() })
+    );
+    assert_eq!(feed.next_event().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn confirmation_matches_the_last_subscription() {
+    let first = r#"{"type":"subscriptions","channels":[{"name":"ticker","product_ids":["BTC-USD"]}]}"#;
+    let second = r#"{"type":"subscriptions","channels":[{"name":"ticker","product_ids":["BTC-USD"]},{"name":"level2","product_ids":["ETH-USD"]}]}"#;
+    let (url, _) = serve(vec![Message::Text(first.into()), Message::Text(second.into())]).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap();
+
+    feed.subscribe(&["BTC-USD"], &["ticker"]).await.unwrap();
+    let subscriptions = feed.subscribe_channels(&["ETH-USD"], &["level2".into()]).await.unwrap();
+    assert_eq!(subscriptions.len(), 2);
+    assert_eq!(subscriptions[1].name, "level2");
+
+    assert_eq!(feed.text().await.unwrap().as_deref(), Some(first));
+    assert_eq!(feed.subscriptions().len(), 2);
+}
+
+#[tokio::test]
+async fn auto_heartbeat_covers_per_channel_products() {
+    let ack = r#"{"type":"subscriptions","channels":[{"name":"heartbeat","product_ids":["ETH-USD"]}]}"#;
+    let (url, server) = serve(vec![Message::Text(ack.into())]).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap().auto_heartbeat(true);
+
+    feed.subscribe_channels(&[], &[ChannelSpec::Products { name: "ticker", product_ids: &["ETH-USD"] }]).await.unwrap();
+    feed.close().await.unwrap();
+
+    let subscribe: serde_json::Value = match &server.await.unwrap()[0] {
+        Message::Text(text) => serde_json::from_str(text).unwrap(),
+        message => panic!("unexpected {:?}", message),
+    };
+    assert_eq!(subscribe["channels"][1], serde_json::json!({"name": "heartbeat", "product_ids": ["ETH-USD"]}));
+}
+
+#[tokio::test]
+async fn stream_tracks_subscriptions_and_heartbeats() {
+    let ack = r#"{"type":"subscriptions","channels":[{"name":"heartbeat","product_ids":["BTC-USD"]}]}"#;
+    let heartbeat = r#"{"type":"heartbeat","product_id":"BTC-USD","sequence":90,"last_trade_id":20}"#;
+    let ticker = r#"{"type":"ticker","product_id":"BTC-USD"}"#;
+    let (url, _) = serve(vec![Message::Text(ack.into()), Message::Text(heartbeat.into()), Message::Text(ticker.into())]).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap().auto_heartbeat(true);
+
+    assert_eq!(feed.next().await.unwrap().unwrap(), Message::Text(ack.into()));
+    assert_eq!(feed.subscriptions()[0].name, "heartbeat");
+
+    assert_eq!(feed.next().await.unwrap().unwrap(), Message::Text(ticker.into()));
+    assert_eq!(feed.heartbeat("BTC-USD").unwrap().sequence, Some(90));
+}
+
+#[tokio::test]
+async fn message_type_decides_over_content() {
+    let ticker = r#"{"type":"ticker","product_id":"BTC-USD","note":"no heartbeat, subscriptions or error here"}"#;
+    let ack = r#"{"type":"subscriptions","channels":[{"name":"ticker","product_ids":["BTC-USD"]}]}"#;
+    let (url, _) = serve(vec![Message::Text(ticker.into()), Message::Text(ack.into())]).await;
+    let mut feed = WebSocketFeed::connect(url).await.unwrap().auto_heartbeat(true);
+
+    assert_eq!(feed.text().await.unwrap().as_deref(), Some(ticker));
+    assert!(feed.subscriptions().is_empty());
+    assert!(feed.heartbeats().is_empty());
+
+    assert_eq!(feed.text().await.unwrap().as_deref(), Some(ack));
+    assert_eq!(feed.subscriptions()[0].name, "ticker");
+}